@@ -2,9 +2,15 @@ use bevy::prelude::*;
 use bevy_ecs_tilemap::TilemapPlugin;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_rapier2d::prelude::*;
-use plugins::{EnemyPlugin, HealthPlugin, PlayerPlugin, SkillsPlugin};
+use plugins::{
+    BossPlugin, ChangeMap, CharacterStatsPlugin, ChestPlugin, EnemyAiPlugin, EnemyAttackPlugin,
+    EnemyPlugin, EnemySpawnerPlugin, ExperiencePlugin, HealthPlugin, MapTransitionPlugin,
+    NavigationPlugin, PlayerPlugin, SkillsPlugin, StatusEffectsPlugin, TerrainPlugin,
+};
 
 mod plugins;
+#[cfg(test)]
+mod test_utils;
 mod tiled;
 
 fn main() {
@@ -21,28 +27,30 @@ fn main() {
         .add_startup_system(startup)
         .add_plugin(TilemapPlugin)
         .add_plugin(tiled::TiledMapPlugin)
+        .add_plugin(MapTransitionPlugin)
+        .add_plugin(NavigationPlugin)
         .add_plugin(PlayerPlugin)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         .add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(CharacterStatsPlugin)
         .add_plugin(HealthPlugin)
         // .add_plugin(FrameTimeDiagnosticsPlugin::default())
         // .add_plugin(LogDiagnosticsPlugin::default())
         .add_plugin(SkillsPlugin)
         .add_plugin(EnemyPlugin)
+        .add_plugin(EnemyAiPlugin)
+        .add_plugin(EnemyAttackPlugin)
+        .add_plugin(EnemySpawnerPlugin)
+        .add_plugin(BossPlugin)
+        .add_plugin(ExperiencePlugin)
+        .add_plugin(ChestPlugin)
+        .add_plugin(TerrainPlugin)
+        .add_plugin(StatusEffectsPlugin)
         .run();
 }
 
-fn startup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    let map_handle: Handle<tiled::TiledMap> = asset_server.load("map.tmx");
-
-    commands.spawn(tiled::TiledMapBundle {
-        tiled_map: map_handle,
-        transform: Transform {
-            scale: Vec3::new(1600.0, 900.0, 0.0),
-            ..Default::default()
-        },
-        ..Default::default()
-    });
+fn startup(mut change_map_events: EventWriter<ChangeMap>) {
+    change_map_events.send(ChangeMap::new("map.tmx"));
 }
 
 #[derive(Debug, Resource)]
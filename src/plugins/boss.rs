@@ -0,0 +1,373 @@
+// Bosses are enemies whose archetype has `phases`. Each phase starts once the boss's health drops
+// to its threshold and swaps the boss's attack and movement.
+//
+// A boss fight can lock the player in an arena:
+// - `BossArena` rectangle objects in Tiled, with an `arena` property naming them.
+// - `BossDoor` rectangle objects with the `arena` property of the arena they close.
+// The doors close when the player enters an arena whose boss is alive. They open once every boss
+// that lives in it is dead, or when the player leaves it by respawning, which resets the bosses to
+// their first phase and full health.
+use bevy::{ecs::system::EntityCommands, math::Rect, prelude::*, sprite::Anchor};
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, RigidBody};
+use serde::Deserialize;
+
+use crate::tiled::{TiledObject, TiledObjectAppExt, Wall};
+
+use super::{
+    character_stats::{Health, MaxHealth, Stat, Stats},
+    enemy::{Enemy, EnemyKind},
+    enemy_ai::{EnemyAi, EnemyAiConfig},
+    enemy_archetype::EnemyArchetype,
+    enemy_attack::{AttackTimers, EnemyAttack},
+    player::Player,
+};
+
+pub struct BossPlugin;
+
+impl Plugin for BossPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_boss_bar_image)
+            .register_tiled_object("BossArena", |entity, object| {
+                entity.insert(BossArena {
+                    name: arena_name(object),
+                    half_size: object.size / 2.0,
+                    locked: false,
+                });
+            })
+            .register_tiled_object("BossDoor", spawn_boss_door)
+            .add_system(init_bosses)
+            .add_system(reload_bosses)
+            .add_system(update_boss_phases)
+            .add_system(lock_arenas)
+            .add_system(spawn_boss_bar)
+            .add_system(update_boss_bar);
+    }
+}
+
+// The parts of `player-healthbar.png` the boss bar is drawn from, in pixels.
+const BAR_FRAME: [Vec2; 2] = [Vec2::new(9.0, 94.0), Vec2::new(86.0, 113.0)];
+const BAR_FILL: [Vec2; 2] = [Vec2::new(28.0, 90.0), Vec2::new(84.0, 94.0)];
+// Where the fill sits in the frame, from the frame's center.
+const BAR_FILL_OFFSET: Vec2 = Vec2::new(-19.5, 0.5);
+// The bar is drawn at the bottom of the screen, in camera space.
+const BAR_POSITION: Vec3 = Vec3::new(0.0, -125.0, -1.0);
+const BAR_SCALE: f32 = 2.0;
+
+const DOOR_COLOR: Color = Color::rgb(0.27, 0.2, 0.16);
+
+// A stage of a boss fight. Values that aren't set keep the ones of the previous phase.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BossPhase {
+    // The fraction of `MaxHealth` at which the phase starts. Phases are listed from the highest
+    // threshold to the lowest.
+    pub health: f32,
+    #[serde(default)]
+    pub attack: Option<EnemyAttack>,
+    #[serde(default)]
+    pub ai: Option<EnemyAiConfig>,
+    #[serde(default)]
+    pub walk_speed: Option<f32>,
+}
+
+#[derive(Debug, Component)]
+pub struct Boss {
+    pub phases: Vec<BossPhase>,
+    // How many phases have started, so 0 while the boss fights with its archetype's stats.
+    pub phase: usize,
+}
+
+#[derive(Debug, Component)]
+pub struct BossArena {
+    pub name: String,
+    pub half_size: Vec2,
+    pub locked: bool,
+}
+
+impl BossArena {
+    fn contains(&self, center: Vec2, position: Vec2) -> bool {
+        (position - center).abs().cmple(self.half_size).all()
+    }
+}
+
+// Blocks the way out of an arena while it's locked.
+#[derive(Debug, Component)]
+pub struct BossDoor {
+    pub arena: String,
+    pub half_size: Vec2,
+}
+
+fn arena_name(object: &TiledObject) -> String {
+    match object.get_property("arena") {
+        Some(tiled::PropertyValue::StringValue(arena)) => arena.clone(),
+        _ => String::new(),
+    }
+}
+
+// Doors start open, so they are hidden and don't collide.
+fn spawn_boss_door(entity: &mut EntityCommands, object: &TiledObject) {
+    entity
+        .insert(BossDoor {
+            arena: arena_name(object),
+            half_size: object.size / 2.0,
+        })
+        .insert(Visibility { is_visible: false })
+        .with_children(|builder| {
+            builder.spawn(SpriteBundle {
+                sprite: Sprite {
+                    color: DOOR_COLOR,
+                    custom_size: Some(object.size),
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 0.0, 0.5),
+                ..Default::default()
+            });
+        });
+}
+
+fn init_bosses(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &EnemyKind), Added<Enemy>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for (entity, kind) in enemy_query.iter() {
+        let Some(archetype) = archetypes.get(&kind.0) else {
+            continue;
+        };
+        if archetype.phases.is_empty() {
+            continue;
+        }
+
+        commands.entity(entity).insert(Boss {
+            phases: archetype.phases.clone(),
+            phase: 0,
+        });
+    }
+}
+
+// Reloading an archetype resets the stats of its enemies to the archetype's, so their bosses go
+// through their phases again, up to the one their health is at.
+fn reload_bosses(
+    mut commands: Commands,
+    mut archetype_events: EventReader<AssetEvent<EnemyArchetype>>,
+    enemy_query: Query<(Entity, &EnemyKind), With<Enemy>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for event in archetype_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let Some(archetype) = archetypes.get(handle) else {
+            continue;
+        };
+
+        for (entity, kind) in enemy_query.iter() {
+            if &kind.0 != handle {
+                continue;
+            }
+
+            let mut enemy_commands = commands.entity(entity);
+            match archetype.phases.is_empty() {
+                true => enemy_commands.remove::<Boss>(),
+                false => enemy_commands.insert(Boss {
+                    phases: archetype.phases.clone(),
+                    phase: 0,
+                }),
+            };
+        }
+    }
+}
+
+fn update_boss_phases(
+    mut commands: Commands,
+    mut boss_query: Query<(Entity, &mut Boss, &mut Stats, &Health, &MaxHealth)>,
+) {
+    for (entity, mut boss, mut stats, health, max_health) in boss_query.iter_mut() {
+        let fraction = health.0 / max_health.0;
+        let phase = boss
+            .phases
+            .iter()
+            .take_while(|phase| fraction <= phase.health)
+            .count();
+        if phase <= boss.phase {
+            continue;
+        }
+
+        // A big hit can skip phases, their changes still apply in order.
+        let mut boss_commands = commands.entity(entity);
+        for phase in &boss.phases[boss.phase..phase] {
+            if let Some(attack) = &phase.attack {
+                boss_commands.insert((AttackTimers::new(attack), attack.clone()));
+                stats.set_base(Stat::AttackDamage, attack.damage);
+            }
+            if let Some(ai) = &phase.ai {
+                boss_commands.insert(ai.clone());
+            }
+            if let Some(walk_speed) = phase.walk_speed {
+                stats.set_base(Stat::WalkSpeed, walk_speed);
+            }
+        }
+
+        info!("Boss entered phase {phase}");
+        boss.phase = phase;
+    }
+}
+
+fn lock_arenas(
+    mut commands: Commands,
+    mut arena_query: Query<(&mut BossArena, &GlobalTransform)>,
+    door_query: Query<(Entity, &BossDoor)>,
+    mut boss_query: Query<(
+        Entity,
+        &EnemyAi,
+        &EnemyKind,
+        &mut Boss,
+        &mut Stats,
+        &mut Health,
+    )>,
+    player_query: Query<&Transform, With<Player>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for (mut arena, arena_transform) in arena_query.iter_mut() {
+        let center = arena_transform.translation().truncate();
+
+        // Bosses belong to the arena they were spawned in, even when they chase the player out.
+        let boss_alive = boss_query
+            .iter()
+            .any(|(_, ai, ..)| arena.contains(center, ai.home));
+        // The player can only leave a locked arena by dying and respawning outside of it.
+        let player_inside = player_query
+            .iter()
+            .any(|transform| arena.contains(center, transform.translation.truncate()));
+
+        let locked = boss_alive && player_inside;
+        if locked == arena.locked {
+            continue;
+        }
+        arena.locked = locked;
+
+        // The fight starts over the next time the player comes in.
+        if !locked {
+            for (entity, ai, kind, mut boss, mut stats, mut health) in boss_query.iter_mut() {
+                if !arena.contains(center, ai.home) {
+                    continue;
+                }
+                let Some(archetype) = archetypes.get(&kind.0) else {
+                    continue;
+                };
+
+                commands.entity(entity).insert((
+                    archetype.ai.clone(),
+                    AttackTimers::new(&archetype.attack),
+                    archetype.attack.clone(),
+                ));
+                stats.set_base(Stat::WalkSpeed, archetype.walk_speed);
+                stats.set_base(Stat::AttackDamage, archetype.attack.damage);
+                health.0 = stats.get(Stat::MaxHealth);
+                boss.phase = 0;
+            }
+        }
+
+        for (door, boss_door) in door_query.iter() {
+            if boss_door.arena != arena.name {
+                continue;
+            }
+
+            let mut door_commands = commands.entity(door);
+            door_commands.insert(Visibility { is_visible: locked });
+            match locked {
+                true => door_commands.insert((
+                    Wall,
+                    RigidBody::Fixed,
+                    Collider::cuboid(boss_door.half_size.x, boss_door.half_size.y),
+                    ActiveEvents::COLLISION_EVENTS,
+                )),
+                false => door_commands.remove::<(Wall, RigidBody, Collider, ActiveEvents)>(),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Resource)]
+struct BossBarImage(Handle<Image>);
+
+fn load_boss_bar_image(mut commands: Commands, assets: Res<AssetServer>) {
+    commands.insert_resource(BossBarImage(assets.load("player-healthbar.png")));
+}
+
+#[derive(Debug, Component)]
+struct BossBar;
+
+#[derive(Debug, Component)]
+struct BossBarFill;
+
+// The bar is a child of the camera, so it stays in place on screen.
+fn spawn_boss_bar(
+    mut commands: Commands,
+    camera_query: Query<Entity, Added<Camera2d>>,
+    bar_image: Res<BossBarImage>,
+) {
+    for camera in camera_query.iter() {
+        commands.entity(camera).with_children(|builder| {
+            builder
+                .spawn((
+                    SpatialBundle {
+                        transform: Transform::from_translation(BAR_POSITION)
+                            .with_scale(Vec3::splat(BAR_SCALE)),
+                        visibility: Visibility { is_visible: false },
+                        ..Default::default()
+                    },
+                    BossBar,
+                    Name::new("Boss Bar"),
+                ))
+                .with_children(|builder| {
+                    builder.spawn(SpriteBundle {
+                        sprite: Sprite {
+                            rect: Some(Rect::from_corners(BAR_FRAME[0], BAR_FRAME[1])),
+                            ..Default::default()
+                        },
+                        texture: bar_image.0.clone(),
+                        ..Default::default()
+                    });
+                    builder.spawn((
+                        SpriteBundle {
+                            sprite: Sprite {
+                                rect: Some(Rect::from_corners(BAR_FILL[0], BAR_FILL[1])),
+                                anchor: Anchor::CenterLeft,
+                                ..Default::default()
+                            },
+                            texture: bar_image.0.clone(),
+                            transform: Transform::from_translation(BAR_FILL_OFFSET.extend(0.1)),
+                            ..Default::default()
+                        },
+                        BossBarFill,
+                    ));
+                });
+        });
+    }
+}
+
+// Shows the health of the boss that is fighting the player, if any.
+fn update_boss_bar(
+    boss_query: Query<(&EnemyAi, &Health, &MaxHealth), With<Boss>>,
+    mut bar_query: Query<&mut Visibility, With<BossBar>>,
+    mut fill_query: Query<&mut Sprite, With<BossBarFill>>,
+) {
+    let fighting_boss = boss_query.iter().find(|(ai, _, _)| ai.target().is_some());
+
+    for mut visibility in bar_query.iter_mut() {
+        visibility.is_visible = fighting_boss.is_some();
+    }
+
+    let Some((_, health, max_health)) = fighting_boss else {
+        return;
+    };
+    let fraction = (health.0 / max_health.0).clamp(0.0, 1.0);
+
+    for mut sprite in fill_query.iter_mut() {
+        let width = (BAR_FILL[1].x - BAR_FILL[0].x) * fraction;
+        sprite.rect = Some(Rect::from_corners(
+            BAR_FILL[0],
+            Vec2::new(BAR_FILL[0].x + width, BAR_FILL[1].y),
+        ));
+    }
+}
@@ -1,13 +1,417 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
 
-#[derive(Debug, Component)]
-pub struct Damage(pub f32);
+pub struct CharacterStatsPlugin;
+
+impl Plugin for CharacterStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_to_stage(CoreStage::PreUpdate, apply_stats);
+    }
+}
+
+// The damage dealt by a projectile or hazard when it hits.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Damage {
+    pub amount: f32,
+    pub kind: DamageKind,
+}
+
+impl Damage {
+    pub fn new(amount: f32, kind: DamageKind) -> Self {
+        Self { amount, kind }
+    }
+}
 
 #[derive(Debug, Component)]
 pub struct Health(pub f32);
 
+// The components below are derived from `Stats`, don't change them directly.
 #[derive(Debug, Component)]
 pub struct MaxHealth(pub f32);
 
 #[derive(Debug, Component)]
 pub struct WalkSpeed(pub f32);
+
+// How fast the character's projectiles fly.
+#[derive(Debug, Component)]
+pub struct SpellSpeed(pub f32);
+
+// The damage of the character's attacks and spells.
+#[derive(Debug, Component)]
+pub struct AttackDamage(pub f32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Stat {
+    MaxHealth,
+    WalkSpeed,
+    SpellSpeed,
+    AttackDamage,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModifierKind {
+    // Added to the base value.
+    Flat,
+    // Multiplies the base value and flat bonuses, 0.1 adds 10%. Percent bonuses add up with each
+    // other rather than compounding.
+    Percent,
+}
+
+#[derive(Debug, Clone)]
+pub struct StatModifier {
+    pub stat: Stat,
+    pub kind: ModifierKind,
+    pub value: f32,
+    // What the modifier comes from, like an item or a buff, so it can be removed along with it.
+    pub source: String,
+}
+
+impl StatModifier {
+    pub fn flat(stat: Stat, value: f32, source: impl Into<String>) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Flat,
+            value,
+            source: source.into(),
+        }
+    }
+
+    pub fn percent(stat: Stat, value: f32, source: impl Into<String>) -> Self {
+        Self {
+            stat,
+            kind: ModifierKind::Percent,
+            value,
+            source: source.into(),
+        }
+    }
+}
+
+// The base values of a character's stats and the modifiers stacked on them. The final values are
+// copied into `MaxHealth`, `WalkSpeed`, `SpellSpeed` and `AttackDamage` whenever they change.
+#[derive(Debug, Default, Component)]
+pub struct Stats {
+    base: HashMap<Stat, f32>,
+    modifiers: Vec<StatModifier>,
+}
+
+impl Stats {
+    pub fn new(base: impl IntoIterator<Item = (Stat, f32)>) -> Self {
+        Self {
+            base: base.into_iter().collect(),
+            modifiers: Vec::new(),
+        }
+    }
+
+    pub fn base(&self, stat: Stat) -> f32 {
+        self.base.get(&stat).copied().unwrap_or(0.0)
+    }
+
+    pub fn set_base(&mut self, stat: Stat, value: f32) {
+        self.base.insert(stat, value);
+    }
+
+    pub fn add_modifier(&mut self, modifier: StatModifier) {
+        self.modifiers.push(modifier);
+    }
+
+    pub fn remove_modifiers(&mut self, source: &str) {
+        self.modifiers.retain(|modifier| modifier.source != source);
+    }
+
+    pub fn get(&self, stat: Stat) -> f32 {
+        let (flat, percent) = self
+            .modifiers
+            .iter()
+            .filter(|modifier| modifier.stat == stat)
+            .fold((0.0, 0.0), |(flat, percent), modifier| {
+                match modifier.kind {
+                    ModifierKind::Flat => (flat + modifier.value, percent),
+                    ModifierKind::Percent => (flat, percent + modifier.value),
+                }
+            });
+
+        ((self.base(stat) + flat) * (1.0 + percent)).max(0.0)
+    }
+}
+
+// Keeps a single percent modifier for `source`, removed when `percent` is 0. The stats are only
+// changed when the modifier is, so effects can set theirs every frame without making
+// `apply_stats` run every frame.
+pub fn set_percent_modifier(stats: &mut Mut<Stats>, stat: Stat, percent: f32, source: &str) {
+    let mut modifiers = stats
+        .modifiers
+        .iter()
+        .filter(|modifier| modifier.source == source);
+    let unchanged = match (modifiers.next(), modifiers.next()) {
+        (None, _) => percent == 0.0,
+        (Some(modifier), None) => {
+            modifier.stat == stat
+                && modifier.kind == ModifierKind::Percent
+                && modifier.value == percent
+        }
+        _ => false,
+    };
+    if unchanged {
+        return;
+    }
+
+    stats.remove_modifiers(source);
+    if percent != 0.0 {
+        stats.add_modifier(StatModifier::percent(stat, percent, source));
+    }
+}
+
+fn apply_stats(
+    mut commands: Commands,
+    mut stats_query: Query<
+        (Entity, &Stats, Option<&MaxHealth>, Option<&mut Health>),
+        Changed<Stats>,
+    >,
+) {
+    for (entity, stats, previous_max_health, health) in stats_query.iter_mut() {
+        let max_health = stats.get(Stat::MaxHealth);
+
+        // Raising the maximum heals by the difference, lowering it only caps the health.
+        if let Some(mut health) = health {
+            let previous_max_health = previous_max_health.map_or(max_health, |max| max.0);
+            health.0 = (health.0 + (max_health - previous_max_health).max(0.0)).min(max_health);
+        }
+
+        commands.entity(entity).insert((
+            MaxHealth(max_health),
+            WalkSpeed(stats.get(Stat::WalkSpeed)),
+            SpellSpeed(stats.get(Stat::SpellSpeed)),
+            AttackDamage(stats.get(Stat::AttackDamage)),
+        ));
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+pub enum DamageKind {
+    #[default]
+    Physical,
+    Fire,
+    Ice,
+    Poison,
+    Lightning,
+}
+
+impl DamageKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "physical" => Some(Self::Physical),
+            "fire" => Some(Self::Fire),
+            "ice" => Some(Self::Ice),
+            "poison" => Some(Self::Poison),
+            "lightning" => Some(Self::Lightning),
+            _ => None,
+        }
+    }
+}
+
+// The fraction of each kind of damage the character ignores. 1 makes it immune, and negative values
+// make it take extra damage, down to double at -1. Kinds that aren't listed aren't resisted.
+#[derive(Debug, Default, Clone, Component, Deserialize)]
+#[serde(transparent)]
+pub struct Resistances(pub HashMap<DamageKind, f32>);
+
+impl Resistances {
+    pub fn get(&self, kind: DamageKind) -> f32 {
+        self.0.get(&kind).copied().unwrap_or(0.0).clamp(-1.0, 1.0)
+    }
+}
+
+// Reduces physical damage with diminishing returns: `ARMOR_SCALE` armor halves it, twice as much
+// leaves a third, and so on.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct Armor(pub f32);
+
+const ARMOR_SCALE: f32 = 50.0;
+
+// Returns the damage left after the target's resistances and armor.
+pub fn mitigate_damage(
+    amount: f32,
+    kind: DamageKind,
+    resistances: Option<&Resistances>,
+    armor: Option<&Armor>,
+) -> f32 {
+    let mut amount = amount * (1.0 - resistances.map_or(0.0, |resistances| resistances.get(kind)));
+
+    if kind == DamageKind::Physical {
+        if let Some(armor) = armor {
+            amount *= ARMOR_SCALE / (ARMOR_SCALE + armor.0.max(0.0));
+        }
+    }
+
+    amount
+}
+
+// Hits dealt by the character have `chance` to be multiplied by `multiplier`.
+#[derive(Debug, Component)]
+pub struct CriticalHits {
+    pub chance: f32,
+    pub multiplier: f32,
+}
+
+// How long the character can't be hurt again after a hit.
+#[derive(Debug, Component)]
+pub struct InvulnerabilityFrames {
+    pub seconds: f32,
+}
+
+// Absorbs damage before health does, until it runs out.
+#[derive(Debug, Component)]
+pub struct Shield(pub f32);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resistances(values: &[(DamageKind, f32)]) -> Resistances {
+        Resistances(values.iter().copied().collect())
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn full_resistance_makes_immune() {
+        let resistances = resistances(&[(DamageKind::Fire, 1.0)]);
+
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Fire, Some(&resistances), None),
+            0.0,
+        );
+    }
+
+    #[test]
+    fn negative_resistance_adds_damage_up_to_double() {
+        let weak = resistances(&[(DamageKind::Fire, -0.5)]);
+        let very_weak = resistances(&[(DamageKind::Fire, -3.0)]);
+
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Fire, Some(&weak), None),
+            15.0,
+        );
+        assert_close(very_weak.get(DamageKind::Fire), -1.0);
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Fire, Some(&very_weak), None),
+            20.0,
+        );
+    }
+
+    #[test]
+    fn unlisted_kinds_are_not_resisted() {
+        let resistances = resistances(&[(DamageKind::Fire, 0.5)]);
+
+        assert_close(resistances.get(DamageKind::Ice), 0.0);
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Ice, Some(&resistances), None),
+            10.0,
+        );
+        assert_close(mitigate_damage(10.0, DamageKind::Ice, None, None), 10.0);
+    }
+
+    #[test]
+    fn armor_has_diminishing_returns() {
+        let damage = |armor| mitigate_damage(12.0, DamageKind::Physical, None, Some(&Armor(armor)));
+
+        assert_close(damage(0.0), 12.0);
+        assert_close(damage(ARMOR_SCALE), 6.0);
+        assert_close(damage(2.0 * ARMOR_SCALE), 4.0);
+    }
+
+    #[test]
+    fn armor_only_reduces_physical_damage() {
+        let armor = Armor(ARMOR_SCALE);
+
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Fire, None, Some(&armor)),
+            10.0,
+        );
+    }
+
+    #[test]
+    fn negative_armor_counts_as_none() {
+        assert_close(
+            mitigate_damage(10.0, DamageKind::Physical, None, Some(&Armor(-25.0))),
+            10.0,
+        );
+    }
+
+    #[test]
+    fn resistances_apply_before_armor() {
+        let resistances = resistances(&[(DamageKind::Physical, 0.5)]);
+
+        assert_close(
+            mitigate_damage(
+                20.0,
+                DamageKind::Physical,
+                Some(&resistances),
+                Some(&Armor(ARMOR_SCALE)),
+            ),
+            5.0,
+        );
+    }
+
+    fn walk_speed(base: f32) -> Stats {
+        Stats::new([(Stat::WalkSpeed, base)])
+    }
+
+    #[test]
+    fn unmodified_stats_are_their_base() {
+        let stats = walk_speed(50.0);
+
+        assert_close(stats.get(Stat::WalkSpeed), 50.0);
+        assert_close(stats.get(Stat::AttackDamage), 0.0);
+    }
+
+    #[test]
+    fn percent_modifiers_scale_base_and_flat_bonuses() {
+        let mut stats = walk_speed(50.0);
+        stats.add_modifier(StatModifier::flat(Stat::WalkSpeed, 10.0, "boots"));
+        stats.add_modifier(StatModifier::flat(Stat::WalkSpeed, 20.0, "level"));
+        stats.add_modifier(StatModifier::percent(Stat::WalkSpeed, 0.5, "haste"));
+        stats.add_modifier(StatModifier::percent(Stat::WalkSpeed, -0.25, "slow"));
+        stats.add_modifier(StatModifier::flat(Stat::AttackDamage, 5.0, "sword"));
+
+        // Percents add up to +25% instead of compounding.
+        assert_close(stats.get(Stat::WalkSpeed), 100.0);
+        assert_close(stats.get(Stat::AttackDamage), 5.0);
+    }
+
+    #[test]
+    fn stats_never_go_below_zero() {
+        let mut stats = walk_speed(50.0);
+        stats.add_modifier(StatModifier::percent(Stat::WalkSpeed, -1.5, "stun"));
+        assert_close(stats.get(Stat::WalkSpeed), 0.0);
+
+        let mut stats = walk_speed(50.0);
+        stats.add_modifier(StatModifier::flat(Stat::WalkSpeed, -80.0, "curse"));
+        assert_close(stats.get(Stat::WalkSpeed), 0.0);
+    }
+
+    #[test]
+    fn removes_every_modifier_of_a_source() {
+        let mut stats = walk_speed(50.0);
+        stats.add_modifier(StatModifier::flat(Stat::WalkSpeed, 10.0, "level"));
+        stats.add_modifier(StatModifier::flat(Stat::AttackDamage, 2.0, "level"));
+        stats.add_modifier(StatModifier::percent(Stat::WalkSpeed, -0.5, "slow"));
+
+        stats.remove_modifiers("level");
+        assert_close(stats.get(Stat::WalkSpeed), 25.0);
+        assert_close(stats.get(Stat::AttackDamage), 0.0);
+
+        // Removing a source without modifiers changes nothing.
+        stats.remove_modifiers("haste");
+        assert_close(stats.get(Stat::WalkSpeed), 25.0);
+
+        stats.remove_modifiers("slow");
+        assert_close(stats.get(Stat::WalkSpeed), 50.0);
+        assert_close(stats.base(Stat::WalkSpeed), 50.0);
+    }
+}
@@ -0,0 +1,52 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{Collider, RigidBody};
+
+use crate::tiled::{TiledObjectAppExt, YSort};
+
+pub struct ChestPlugin;
+
+impl Plugin for ChestPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system_to_stage(StartupStage::PreStartup, load_spritesheet)
+            .register_tiled_object("Chest", |entity, _| {
+                entity.insert(Chest);
+            })
+            .add_system(spawn_chests);
+    }
+}
+
+#[derive(Debug, Resource)]
+pub struct ChestSpriteSheet(Handle<TextureAtlas>);
+
+fn load_spritesheet(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut texture_atlas: ResMut<Assets<TextureAtlas>>,
+) {
+    let image = assets.load("chest_01.png");
+    let atlas = TextureAtlas::from_grid(image, Vec2::new(16.0, 16.0), 4, 1, None, None);
+
+    let atlas_handle = texture_atlas.add(atlas);
+    commands.insert_resource(ChestSpriteSheet(atlas_handle));
+}
+
+#[derive(Debug, Component)]
+pub struct Chest;
+
+fn spawn_chests(
+    mut commands: Commands,
+    chest_query: Query<Entity, Added<Chest>>,
+    chest_sheet: Res<ChestSpriteSheet>,
+) {
+    for chest in chest_query.iter() {
+        commands
+            .entity(chest)
+            .insert((
+                TextureAtlasSprite::new(0),
+                chest_sheet.0.clone(),
+                RigidBody::Fixed,
+                Collider::cuboid(6.0, 5.0),
+                YSort::new(-8.0),
+            ));
+    }
+}
@@ -1,44 +1,55 @@
 use super::{
-    character_stats::{Health, MaxHealth, WalkSpeed},
-    health::{create_bar_sprite, Bar, HealthSpriteSheet},
-    player::{FacingDirection, Player},
+    character_stats::{Armor, Health, Stat, Stats},
+    enemy_ai::{EnemyAi, EnemyGroup},
+    enemy_archetype::{EnemyArchetype, EnemyArchetypeLoader},
+    enemy_attack::AttackTimers,
+    health::{create_bar_sprite, Bar, DeathEvent, HealthSpriteSheet},
+    map_transition::MapScoped,
+    navigation::NavPath,
+    player::FacingDirection,
+    status_effects::StatusEffects,
+    terrain::Terrain,
     utils::AnimationTimer,
 };
-use bevy::{prelude::*, sprite::Anchor};
+use crate::tiled::{TiledObjectAppExt, YSort};
+use bevy::{asset::LoadState, prelude::*, sprite::Anchor};
 use bevy_rapier2d::prelude::{
     ActiveEvents, Collider, KinematicCharacterController, KinematicCharacterControllerOutput,
     RigidBody,
 };
-use rand::Rng;
 
 pub struct EnemyPlugin;
-pub const TILE_SIZE: f32 = 16.0;
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system_to_stage(StartupStage::PreStartup, load_spritesheet)
-            .add_startup_system(spawn_enemies)
-            .add_system(handle_alerted)
-            .add_system(random_walking)
+        app.add_asset::<EnemyArchetype>()
+            .add_asset_loader(EnemyArchetypeLoader)
+            .register_tiled_object("Enemy", |entity, object| {
+                // The `kind` property names the archetype in `assets/enemies`.
+                let kind = match object.get_property("kind") {
+                    Some(tiled::PropertyValue::StringValue(kind)) => kind.clone(),
+                    _ => DEFAULT_ENEMY_KIND.to_string(),
+                };
+                // Enemies with the same `group` property alert each other.
+                let group = match object.get_property("group") {
+                    Some(tiled::PropertyValue::StringValue(group)) => group.clone(),
+                    _ => String::new(),
+                };
+                entity.insert(EnemySpawnPoint {
+                    kind,
+                    group: EnemyGroup(group),
+                });
+            })
+            .add_system(spawn_enemies)
+            .add_system(build_enemies)
+            .add_system(reload_enemies)
             .add_system(animate_sprite)
-            .add_system(handle_facing_direction);
+            .add_system(handle_facing_direction)
+            .add_system_to_stage(CoreStage::PostUpdate, drop_loot);
     }
 }
 
-#[derive(Debug, Resource)]
-pub struct EnemySpriteSheet(Handle<TextureAtlas>);
-
-pub fn load_spritesheet(
-    mut commands: Commands,
-    assets: Res<AssetServer>,
-    mut texture_atlas: ResMut<Assets<TextureAtlas>>,
-) {
-    let image = assets.load("Dungeon.png");
-    let atlas = TextureAtlas::from_grid(image, Vec2::new(16.0, 16.0), 31, 2, None, None);
-
-    let atlas_handle = texture_atlas.add(atlas);
-    commands.insert_resource(EnemySpriteSheet(atlas_handle));
-}
+const DEFAULT_ENEMY_KIND: &str = "slime";
 
 #[derive(Debug, Component)]
 pub struct Enemy {
@@ -46,115 +57,193 @@ pub struct Enemy {
     finished_move: bool,
 }
 
-#[derive(Debug, Component, PartialEq, Eq)]
-pub enum AggroStatus {
-    Alerted(u32),
-    Neutral,
-}
+// The archetype an enemy was spawned from.
+#[derive(Debug, Component)]
+pub struct EnemyKind(pub Handle<EnemyArchetype>);
 
+// The frames of the walk animation, from the archetype.
 #[derive(Debug, Component)]
-pub struct WalkTime(Timer);
+pub struct EnemyFrames {
+    first: usize,
+    last: usize,
+}
 
 #[derive(Debug, Component)]
-pub struct WalkDirection(f32, f32);
+pub struct EnemySpawnPoint {
+    pub kind: String,
+    pub group: EnemyGroup,
+}
 
 pub fn spawn_enemies(
     mut commands: Commands,
-    enemy_sheet: Res<EnemySpriteSheet>,
-    health_spritesheet: Res<HealthSpriteSheet>,
+    spawn_point_query: Query<(&Transform, &EnemySpawnPoint), Added<EnemySpawnPoint>>,
+    asset_server: Res<AssetServer>,
 ) {
-    for _ in 0..5 {
-        spawn_enemy(&mut commands, &enemy_sheet, &health_spritesheet);
+    for (spawn_transform, spawn_point) in spawn_point_query.iter() {
+        spawn_enemy_of_kind(
+            &mut commands,
+            &asset_server,
+            &spawn_point.kind,
+            spawn_transform.translation.truncate(),
+            spawn_point.group.clone(),
+        );
     }
 }
 
-pub fn spawn_enemy(
+// Spawns an enemy of the archetype in `assets/enemies/<kind>.enemy.ron`. The enemy stays hidden
+// and inactive until the archetype is loaded, see `build_enemies`.
+pub fn spawn_enemy_of_kind(
     commands: &mut Commands,
-    enemy_sheet: &Res<EnemySpriteSheet>,
-    health_spritesheet: &Res<HealthSpriteSheet>,
-) {
-    let mut sprite = TextureAtlasSprite {
-        index: 55,
-        anchor: Anchor::Custom(Vec2::new(0.0, -0.2)),
-        ..Default::default()
-    };
-    sprite.custom_size = Some(Vec2::new(TILE_SIZE, TILE_SIZE * 2.5));
-
-    let mut rng = rand::thread_rng();
-    let x = rng.gen_range(-100.0..100.0);
-    let y = rng.gen_range(-100.0..100.0);
+    asset_server: &AssetServer,
+    kind: &str,
+    position: Vec2,
+    group: EnemyGroup,
+) -> Entity {
+    let archetype: Handle<EnemyArchetype> = asset_server.load(format!("enemies/{kind}.enemy.ron"));
 
     commands
         .spawn((
-            SpriteSheetBundle {
-                sprite,
-                texture_atlas: enemy_sheet.0.clone(),
-                transform: Transform {
-                    translation: Vec3::new(x, y, 0.1),
-                    ..Default::default()
-                },
+            SpatialBundle {
+                transform: Transform::from_translation(position.extend(0.0)),
                 ..Default::default()
             },
-            RigidBody::KinematicPositionBased,
-            Collider::cuboid(TILE_SIZE / 2.5, TILE_SIZE - 3.0),
+            EnemyKind(archetype),
+            EnemyAi::new(position),
+            group,
+            NavPath::default(),
+            MapScoped,
+            Terrain::default(),
+            StatusEffects::default(),
+            Name::new("Enemy"),
         ))
-        .with_children(|builder| {
-            for i in 5..29 {
-                let sprite_bundle = create_bar_sprite(i, &health_spritesheet);
-                builder.spawn(sprite_bundle).insert(Bar(i));
-            }
-        })
-        .insert(ActiveEvents::COLLISION_EVENTS)
-        .insert(Enemy {
-            facing_direction: FacingDirection::Right,
-            finished_move: false,
-        })
-        .insert(KinematicCharacterController {
+        .id()
+}
+
+// The components that come from the archetype, inserted again when it's reloaded.
+fn archetype_bundle(archetype: &EnemyArchetype) -> impl Bundle {
+    let sprite = &archetype.sprite;
+    let size = Vec2::from(sprite.size);
+
+    (
+        TextureAtlasSprite {
+            index: sprite.first_frame,
+            anchor: Anchor::Custom(Vec2::new(0.0, -0.2)),
+            custom_size: Some(size),
             ..Default::default()
-        })
-        .insert(AggroStatus::Neutral)
-        .insert(Name::new("Enemy"))
-        .insert(AnimationTimer(Timer::from_seconds(
-            0.1,
+        },
+        archetype.texture_atlas.clone(),
+        EnemyFrames {
+            first: sprite.first_frame,
+            last: sprite.last_frame,
+        },
+        AnimationTimer(Timer::from_seconds(
+            sprite.frame_seconds,
             TimerMode::Repeating,
-        )))
-        .insert(WalkTime(Timer::from_seconds(4.0, TimerMode::Repeating)))
-        .insert(WalkDirection(1.0, 0.0))
-        .insert(WalkSpeed(5.0))
-        .insert(Health(50.0))
-        .insert(MaxHealth(50.0));
+        )),
+        Collider::cuboid(archetype.collider.0, archetype.collider.1),
+        // Sorted by the feet, which are a bit above the bottom of the frame.
+        YSort::new(-size.y * 0.3),
+        archetype.ai.clone(),
+        AttackTimers::new(&archetype.attack),
+        archetype.attack.clone(),
+        Armor(archetype.armor),
+        archetype.resistances.clone(),
+    )
 }
 
-pub fn random_walking(
-    mut enemy_query: Query<(
-        With<Enemy>,
-        &mut WalkDirection,
-        &mut KinematicCharacterController,
-        &mut WalkTime,
-        &AggroStatus,
-    )>,
-    time: Res<Time>,
+// The base stats of an archetype. Modifiers stacked on them are kept when the archetype reloads.
+fn set_base_stats(stats: &mut Stats, archetype: &EnemyArchetype) {
+    stats.set_base(Stat::MaxHealth, archetype.max_health);
+    stats.set_base(Stat::WalkSpeed, archetype.walk_speed);
+    stats.set_base(Stat::AttackDamage, archetype.attack.damage);
+}
+
+fn build_enemies(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &EnemyKind), Without<Enemy>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+    asset_server: Res<AssetServer>,
+    health_spritesheet: Res<HealthSpriteSheet>,
 ) {
-    let mut rng = rand::thread_rng();
-    for (_, mut walk_direction, mut transform, mut walk_time, status) in enemy_query.iter_mut() {
-        walk_time.0.tick(time.delta());
+    for (entity, kind) in enemy_query.iter() {
+        let Some(archetype) = archetypes.get(&kind.0) else {
+            if asset_server.get_load_state(&kind.0) == LoadState::Failed {
+                error!("Could not spawn enemy, its archetype failed to load.");
+                commands.entity(entity).despawn_recursive();
+            }
+            continue;
+        };
 
-        if status == &AggroStatus::Neutral {
-            let x = walk_direction.0 * time.delta_seconds();
+        let mut stats = Stats::default();
+        set_base_stats(&mut stats, archetype);
 
-            //             enemy.facing_direction = match x >= 0.0 {
-            //                 true => FacingDirection::Right,
-            //                 false => FacingDirection::Left,
-            //             };
+        commands
+            .entity(entity)
+            .insert(archetype_bundle(archetype))
+            .insert((
+                stats,
+                RigidBody::KinematicPositionBased,
+                KinematicCharacterController::default(),
+                ActiveEvents::COLLISION_EVENTS,
+                Health(archetype.max_health),
+                Name::new(archetype.name.clone()),
+                Enemy {
+                    facing_direction: FacingDirection::Right,
+                    finished_move: false,
+                },
+            ))
+            .with_children(|builder| {
+                for i in 5..29 {
+                    let sprite_bundle = create_bar_sprite(i, &health_spritesheet);
+                    builder.spawn(sprite_bundle).insert(Bar(i));
+                }
+            });
+    }
+}
 
-            transform.translation = Some(Vec2::new(x, walk_direction.1 * time.delta_seconds()));
+// Applies changes to archetype files to the enemies already spawned from them.
+fn reload_enemies(
+    mut commands: Commands,
+    mut archetype_events: EventReader<AssetEvent<EnemyArchetype>>,
+    mut enemy_query: Query<(Entity, &EnemyKind, &mut Stats), With<Enemy>>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for event in archetype_events.iter() {
+        let AssetEvent::Modified { handle } = event else {
+            continue;
+        };
+        let Some(archetype) = archetypes.get(handle) else {
+            continue;
+        };
 
-            if walk_time.0.just_finished() {
-                let x = rng.gen_range(-10.0..10.0);
-                let y = rng.gen_range(-10.0..10.0);
-                walk_direction.0 = x;
-                walk_direction.1 = y;
+        for (entity, kind, mut stats) in enemy_query.iter_mut() {
+            if &kind.0 != handle {
+                continue;
             }
+
+            commands.entity(entity).insert(archetype_bundle(archetype));
+            set_base_stats(&mut stats, archetype);
+        }
+    }
+}
+
+// There are no items to pick up yet, so loot is only logged.
+fn drop_loot(
+    mut death_events: EventReader<DeathEvent>,
+    enemy_query: Query<&EnemyKind>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for death in death_events.iter() {
+        let Some(archetype) = enemy_query
+            .get(death.entity)
+            .ok()
+            .and_then(|kind| archetypes.get(&kind.0))
+        else {
+            continue;
+        };
+
+        for item in archetype.roll_loot() {
+            info!("{} dropped {item}", archetype.name);
         }
     }
 }
@@ -176,12 +265,12 @@ fn animate_sprite(
     time: Res<Time>,
     mut query: Query<(
         &Enemy,
+        &EnemyFrames,
         &mut AnimationTimer,
         &mut TextureAtlasSprite,
-        &Handle<TextureAtlas>,
     )>,
 ) {
-    for (player, mut timer, mut sprite, _texture_atlas_handle) in query.iter_mut() {
+    for (player, frames, mut timer, mut sprite) in query.iter_mut() {
         timer.tick(time.delta());
         if timer.just_finished() {
             match player.facing_direction {
@@ -191,50 +280,10 @@ fn animate_sprite(
                 FacingDirection::Right => sprite.flip_x = false,
             };
 
-            sprite.index = match sprite.index == 61 {
-                true => 55,
+            sprite.index = match sprite.index >= frames.last || sprite.index < frames.first {
+                true => frames.first,
                 false => sprite.index + 1,
             };
         }
     }
 }
-
-fn handle_alerted(
-    mut enemy_query: Query<(
-        &AggroStatus,
-        &Transform,
-        &mut KinematicCharacterController,
-        &WalkSpeed,
-        With<Enemy>,
-    )>,
-    player_query: Query<(Entity, &Transform, With<Player>)>,
-    time: Res<Time>,
-) {
-    for (player_entity, player_transform, _) in player_query.iter() {
-        for (status, enemy_transform, mut enemy_character, walk_speed, _) in enemy_query.iter_mut()
-        {
-            if let AggroStatus::Alerted(player_alerted_entity) = status {
-                let enemy_pos = enemy_transform.translation;
-
-                if &player_entity.index() == player_alerted_entity {
-                    let player_pos = player_transform.translation;
-
-                    let x = match (enemy_pos.x - player_pos.x).is_sign_negative() {
-                        true => walk_speed.0,
-                        false => -(walk_speed.0),
-                    };
-
-                    let y = match (enemy_pos.y - player_pos.y).is_sign_negative() {
-                        true => walk_speed.0,
-                        false => -(walk_speed.0),
-                    };
-
-                    enemy_character.translation = Some(Vec2::new(
-                        x * time.delta_seconds(),
-                        y * time.delta_seconds(),
-                    ));
-                }
-            }
-        }
-    }
-}
@@ -0,0 +1,390 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::{KinematicCharacterController, QueryFilter, RapierContext};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::tiled::Wall;
+
+use super::{
+    character_stats::{Health, MaxHealth, WalkSpeed},
+    enemy::Enemy,
+    navigation::{NavGrid, NavPath},
+    player::Player,
+    status_effects::StatusEffects,
+};
+
+pub struct EnemyAiPlugin;
+
+impl Plugin for EnemyAiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EnemyAlerted>()
+            .add_system(notice_targets)
+            .add_system(handle_alerts.after(notice_targets))
+            .add_system(update_enemy_state.after(handle_alerts))
+            .add_system(move_enemies.after(update_enemy_state));
+    }
+}
+
+// How far from a destination an enemy stops walking.
+const ARRIVE_DISTANCE: f32 = 2.0;
+// Enemies keep attacking until the target is this much further than the attack range, so they
+// don't flicker between chasing and attacking at the edge of it.
+pub const ATTACK_RANGE_SLACK: f32 = 1.2;
+// Gives up on patrols and returns that got stuck on something.
+const WALK_TIMEOUT_SECONDS: f32 = 10.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EnemyState {
+    // Stands still until its timer runs out.
+    Idle,
+    // Walks to a random point around home.
+    Patrol(Vec2),
+    Chase(Entity),
+    // In attack range of the target.
+    Attack(Entity),
+    // Runs away from the target when low on health.
+    Flee(Entity),
+    // Walks back home after losing the target. It doesn't look for targets on the way, but
+    // getting hit or alerted by its group still turns it around.
+    Return,
+}
+
+// Tunable parameters of the enemy behaviour. Distances are in pixels.
+#[derive(Debug, Clone, Component, Deserialize)]
+#[serde(default)]
+pub struct EnemyAiConfig {
+    pub idle_seconds: f32,
+    pub patrol_radius: f32,
+    // Multiplies `WalkSpeed` while chasing and fleeing.
+    pub chase_speed: f32,
+    pub attack_range: f32,
+    // How far from home the enemy follows a target before giving up.
+    pub leash_radius: f32,
+    // How long the target can stay out of sight before the enemy gives up.
+    pub lose_sight_seconds: f32,
+    // The fraction of `MaxHealth` below which the enemy flees.
+    pub flee_health: f32,
+    pub flee_seconds: f32,
+    pub sight_radius: f32,
+    // The full width of the vision cone in degrees, centered on the facing direction.
+    pub sight_angle: f32,
+    // How far an alert spreads to other enemies of the same group.
+    pub alert_radius: f32,
+}
+
+impl Default for EnemyAiConfig {
+    fn default() -> Self {
+        Self {
+            idle_seconds: 2.0,
+            patrol_radius: 48.0,
+            chase_speed: 2.0,
+            attack_range: 16.0,
+            leash_radius: 160.0,
+            lose_sight_seconds: 3.0,
+            flee_health: 0.2,
+            flee_seconds: 3.0,
+            sight_radius: 96.0,
+            sight_angle: 120.0,
+            alert_radius: 64.0,
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct EnemyAi {
+    pub state: EnemyState,
+    // The position the enemy patrols around and returns to.
+    pub home: Vec2,
+    // Ends the current state. While chasing, it's reset every time the target is seen.
+    pub timer: Timer,
+    // The direction the enemy looks in, which is the last direction it moved in.
+    pub facing: Vec2,
+}
+
+impl EnemyAi {
+    pub fn new(home: Vec2) -> Self {
+        Self {
+            state: EnemyState::Idle,
+            home,
+            timer: Timer::from_seconds(0.0, TimerMode::Once),
+            facing: Vec2::X,
+        }
+    }
+
+    pub fn set_state(&mut self, state: EnemyState, seconds: f32) {
+        self.state = state;
+        self.timer = Timer::from_seconds(seconds, TimerMode::Once);
+    }
+
+    // Starts chasing a target, unless the enemy is already busy with one.
+    pub fn alert(&mut self, target: Entity, config: &EnemyAiConfig) {
+        if let EnemyState::Idle | EnemyState::Patrol(_) | EnemyState::Return = self.state {
+            self.set_state(EnemyState::Chase(target), config.lose_sight_seconds);
+        }
+    }
+
+    // Returning enemies don't look around, so the player can escape them unless they attack.
+    pub fn is_watching(&self) -> bool {
+        matches!(self.state, EnemyState::Idle | EnemyState::Patrol(_))
+    }
+
+    pub fn target(&self) -> Option<Entity> {
+        match self.state {
+            EnemyState::Chase(target) | EnemyState::Attack(target) | EnemyState::Flee(target) => {
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+}
+
+// Enemies alert the other enemies of their group around them. Enemies without a group all belong
+// to the unnamed one.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Component)]
+pub struct EnemyGroup(pub String);
+
+// Sent when an enemy notices a target or gets attacked by it. The enemy and the enemies of its
+// group in its `alert_radius` start chasing the target.
+#[derive(Debug, Clone, Copy)]
+pub struct EnemyAlerted {
+    pub enemy: Entity,
+    pub target: Entity,
+}
+
+// Casts a ray between two points against the wall colliders of the map.
+pub fn has_line_of_sight(
+    rapier_context: &RapierContext,
+    wall_query: &Query<Entity, With<Wall>>,
+    from: Vec2,
+    to: Vec2,
+) -> bool {
+    let delta = to - from;
+    let distance = delta.length();
+    if distance == 0.0 {
+        return true;
+    }
+
+    let is_wall = |entity| wall_query.contains(entity);
+    let filter = QueryFilter::new().predicate(&is_wall);
+    rapier_context
+        .cast_ray(from, delta / distance, distance, true, filter)
+        .is_none()
+}
+
+fn notice_targets(
+    enemy_query: Query<(Entity, &Transform, &EnemyAi, &EnemyAiConfig), With<Enemy>>,
+    player_query: Query<(Entity, &Transform), With<Player>>,
+    wall_query: Query<Entity, With<Wall>>,
+    rapier_context: Res<RapierContext>,
+    mut alert_events: EventWriter<EnemyAlerted>,
+) {
+    for (enemy, transform, ai, config) in enemy_query.iter() {
+        if !ai.is_watching() {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let half_sight_angle = config.sight_angle.to_radians() / 2.0;
+
+        let noticed = player_query.iter().find(|(_, player_transform)| {
+            let player_position = player_transform.translation.truncate();
+            let to_player = player_position - position;
+            let distance = to_player.length();
+
+            // A player on top of the enemy has no direction, but can't go unnoticed.
+            let in_sight_cone = distance <= f32::EPSILON
+                || ai.facing.angle_between(to_player).abs() <= half_sight_angle;
+
+            distance <= config.sight_radius
+                && in_sight_cone
+                && has_line_of_sight(&rapier_context, &wall_query, position, player_position)
+        });
+
+        if let Some((player, _)) = noticed {
+            alert_events.send(EnemyAlerted {
+                enemy,
+                target: player,
+            });
+        }
+    }
+}
+
+fn handle_alerts(
+    mut alert_events: EventReader<EnemyAlerted>,
+    mut enemy_query: Query<(
+        Entity,
+        &Transform,
+        &mut EnemyAi,
+        &EnemyAiConfig,
+        &EnemyGroup,
+    )>,
+) {
+    for alert in alert_events.iter() {
+        let Ok((_, transform, _, config, group)) = enemy_query.get(alert.enemy) else {
+            continue;
+        };
+        let position = transform.translation.truncate();
+        let alert_radius = config.alert_radius;
+        let group = group.clone();
+
+        for (enemy, enemy_transform, mut ai, enemy_config, enemy_group) in enemy_query.iter_mut() {
+            let in_range =
+                enemy_transform.translation.truncate().distance(position) <= alert_radius;
+
+            if enemy == alert.enemy || (in_range && *enemy_group == group) {
+                ai.alert(alert.target, enemy_config);
+            }
+        }
+    }
+}
+
+fn update_enemy_state(
+    mut enemy_query: Query<
+        (
+            &Transform,
+            &EnemyAiConfig,
+            &mut EnemyAi,
+            &Health,
+            &MaxHealth,
+        ),
+        With<Enemy>,
+    >,
+    target_query: Query<&Transform, Without<Enemy>>,
+    wall_query: Query<Entity, With<Wall>>,
+    nav_grid: Option<Res<NavGrid>>,
+    rapier_context: Res<RapierContext>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for (transform, config, mut ai, health, max_health) in enemy_query.iter_mut() {
+        ai.timer.tick(time.delta());
+
+        let position = transform.translation.truncate();
+        let low_health = health.0 < max_health.0 * config.flee_health;
+        let target_position = ai
+            .target()
+            .and_then(|target| target_query.get(target).ok())
+            .map(|target_transform| target_transform.translation.truncate());
+
+        match (ai.state, target_position) {
+            (EnemyState::Idle, _) if ai.timer.finished() => {
+                // Only patrol to points that can be walked to.
+                let offset = Vec2::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+                let destination = ai.home + offset * config.patrol_radius;
+                let walkable = nav_grid.as_ref().is_none_or(|nav_grid| {
+                    nav_grid.is_walkable(nav_grid.world_to_cell(destination))
+                });
+
+                match walkable {
+                    true => ai.set_state(EnemyState::Patrol(destination), WALK_TIMEOUT_SECONDS),
+                    false => ai.set_state(EnemyState::Idle, config.idle_seconds),
+                }
+            }
+            (EnemyState::Patrol(destination), _)
+                if ai.timer.finished() || position.distance(destination) <= ARRIVE_DISTANCE =>
+            {
+                let idle_seconds = config.idle_seconds * rng.gen_range(0.5..1.5);
+                ai.set_state(EnemyState::Idle, idle_seconds);
+            }
+            (EnemyState::Return, _)
+                if ai.timer.finished() || position.distance(ai.home) <= ARRIVE_DISTANCE =>
+            {
+                ai.set_state(EnemyState::Idle, config.idle_seconds);
+            }
+            // The target despawned.
+            (EnemyState::Chase(_) | EnemyState::Attack(_) | EnemyState::Flee(_), None) => {
+                ai.set_state(EnemyState::Return, WALK_TIMEOUT_SECONDS);
+            }
+            (EnemyState::Chase(target) | EnemyState::Attack(target), Some(_)) if low_health => {
+                ai.set_state(EnemyState::Flee(target), config.flee_seconds);
+            }
+            (EnemyState::Chase(target), Some(target_position)) => {
+                if has_line_of_sight(&rapier_context, &wall_query, position, target_position) {
+                    ai.timer.reset();
+                }
+
+                if ai.timer.finished() || position.distance(ai.home) > config.leash_radius {
+                    ai.set_state(EnemyState::Return, WALK_TIMEOUT_SECONDS);
+                } else if position.distance(target_position) <= config.attack_range {
+                    ai.set_state(EnemyState::Attack(target), 0.0);
+                }
+            }
+            (EnemyState::Attack(target), Some(target_position))
+                if position.distance(target_position)
+                    > config.attack_range * ATTACK_RANGE_SLACK =>
+            {
+                ai.set_state(EnemyState::Chase(target), config.lose_sight_seconds);
+            }
+            (EnemyState::Flee(_), Some(_)) if ai.timer.finished() => {
+                ai.set_state(EnemyState::Return, WALK_TIMEOUT_SECONDS);
+            }
+            _ => (),
+        }
+    }
+}
+
+fn move_enemies(
+    mut enemy_query: Query<
+        (
+            &Transform,
+            &mut EnemyAi,
+            &EnemyAiConfig,
+            &mut NavPath,
+            &mut KinematicCharacterController,
+            &WalkSpeed,
+            &StatusEffects,
+        ),
+        With<Enemy>,
+    >,
+    target_query: Query<&Transform, Without<Enemy>>,
+    nav_grid: Option<Res<NavGrid>>,
+    time: Res<Time>,
+) {
+    for (transform, mut ai, config, mut nav_path, mut controller, walk_speed, status_effects) in
+        enemy_query.iter_mut()
+    {
+        if status_effects.is_stunned() {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let target_position = ai
+            .target()
+            .and_then(|target| target_query.get(target).ok())
+            .map(|target_transform| target_transform.translation.truncate());
+        let nav_grid = nav_grid.as_deref();
+
+        let (direction, speed) = match (ai.state, target_position) {
+            (EnemyState::Patrol(destination), _) => (
+                nav_path.steer(nav_grid, position, destination, time.delta()) - position,
+                1.0,
+            ),
+            (EnemyState::Return, _) => (
+                nav_path.steer(nav_grid, position, ai.home, time.delta()) - position,
+                1.0,
+            ),
+            (EnemyState::Chase(_), Some(target_position)) => (
+                nav_path.steer(nav_grid, position, target_position, time.delta()) - position,
+                config.chase_speed,
+            ),
+            (EnemyState::Flee(_), Some(target_position)) => {
+                (position - target_position, config.chase_speed)
+            }
+            (EnemyState::Attack(_), Some(target_position)) => {
+                if target_position != position {
+                    ai.facing = (target_position - position).normalize();
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        if direction != Vec2::ZERO {
+            ai.facing = direction.normalize();
+        }
+
+        let speed = walk_speed.0 * speed;
+        controller.translation = Some(direction.normalize_or_zero() * speed * time.delta_seconds());
+    }
+}
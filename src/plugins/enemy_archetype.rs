@@ -0,0 +1,148 @@
+use std::path::PathBuf;
+
+use anyhow::Result;
+use bevy::{
+    asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use rand::Rng;
+use serde::Deserialize;
+
+use super::{
+    boss::BossPhase, character_stats::Resistances, enemy_ai::EnemyAiConfig,
+    enemy_attack::EnemyAttack,
+};
+
+// Describes a kind of enemy, loaded from `assets/enemies/<kind>.enemy.ron`.
+#[derive(Debug, Clone, Deserialize, TypeUuid)]
+#[uuid = "5b0a7f4e-3c1d-4f4b-9b8e-2f6d1c9a7e21"]
+pub struct EnemyArchetype {
+    pub name: String,
+    pub sprite: EnemySprite,
+    pub max_health: f32,
+    pub walk_speed: f32,
+    // The half extents of the collider.
+    pub collider: (f32, f32),
+    #[serde(default)]
+    pub armor: f32,
+    #[serde(default)]
+    pub resistances: Resistances,
+    #[serde(default)]
+    pub ai: EnemyAiConfig,
+    #[serde(default)]
+    pub attack: EnemyAttack,
+    #[serde(default)]
+    pub loot: Vec<LootDrop>,
+    // Given to the character that kills the enemy.
+    #[serde(default)]
+    pub experience: u32,
+    // Makes enemies of this kind bosses, see `boss.rs`.
+    #[serde(default)]
+    pub phases: Vec<BossPhase>,
+
+    // Built from `sprite` by the loader.
+    #[serde(skip)]
+    pub texture_atlas: Handle<TextureAtlas>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EnemySprite {
+    // The path of the sprite sheet, relative to the assets folder.
+    pub texture: String,
+    pub tile_size: (f32, f32),
+    pub columns: usize,
+    pub rows: usize,
+    // The walk animation plays the frames from `first_frame` to `last_frame`.
+    pub first_frame: usize,
+    pub last_frame: usize,
+    pub frame_seconds: f32,
+    // The size frames are drawn at.
+    pub size: (f32, f32),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootDrop {
+    pub item: String,
+    // The chance between 0 and 1 that the item drops.
+    pub chance: f32,
+}
+
+impl EnemyArchetype {
+    // Picks the items dropped by a defeated enemy of this kind.
+    pub fn roll_loot(&self) -> Vec<String> {
+        let mut rng = rand::thread_rng();
+
+        self.loot
+            .iter()
+            .filter(|drop| rng.gen::<f32>() < drop.chance)
+            .map(|drop| drop.item.clone())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+pub struct EnemyArchetypeLoader;
+
+impl AssetLoader for EnemyArchetypeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let mut archetype: EnemyArchetype = ron::de::from_bytes(bytes).map_err(|e| {
+                anyhow::anyhow!(
+                    "Could not load enemy archetype {}: {e}",
+                    load_context.path().display()
+                )
+            })?;
+
+            let texture_path = AssetPath::new(PathBuf::from(&archetype.sprite.texture), None);
+            let texture: Handle<Image> = load_context.get_handle(texture_path.clone());
+            let atlas = TextureAtlas::from_grid(
+                texture,
+                Vec2::from(archetype.sprite.tile_size),
+                archetype.sprite.columns,
+                archetype.sprite.rows,
+                None,
+                None,
+            );
+            archetype.texture_atlas =
+                load_context.set_labeled_asset("texture_atlas", LoadedAsset::new(atlas));
+
+            load_context
+                .set_default_asset(LoadedAsset::new(archetype).with_dependency(texture_path));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["enemy.ron"];
+        EXTENSIONS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn loads_bundled_archetypes() {
+        let paths = fs::read_dir("assets/enemies")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.to_string_lossy().ends_with(".enemy.ron"))
+            .collect::<Vec<_>>();
+        assert!(!paths.is_empty());
+
+        for path in paths {
+            let bytes = fs::read(&path).unwrap();
+            if let Err(error) = ron::de::from_bytes::<EnemyArchetype>(&bytes) {
+                panic!("Could not load {}: {error}", path.display());
+            }
+        }
+    }
+}
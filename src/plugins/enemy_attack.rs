@@ -0,0 +1,168 @@
+use bevy::prelude::*;
+use bevy_rapier2d::prelude::RapierContext;
+use serde::Deserialize;
+
+use crate::tiled::Wall;
+
+use super::{
+    character_stats::{AttackDamage, DamageKind, Health},
+    enemy::Enemy,
+    enemy_ai::{has_line_of_sight, EnemyAi, EnemyAiConfig, EnemyState, ATTACK_RANGE_SLACK},
+    health::DamageEvent,
+    skills::{create_fireball, FireSpriteSheet},
+    status_effects::StatusEffects,
+};
+
+pub struct EnemyAttackPlugin;
+
+impl Plugin for EnemyAttackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(enemy_attacks);
+    }
+}
+
+// How far in front of an enemy its projectiles appear, so they don't start inside it.
+const PROJECTILE_SPAWN_DISTANCE: f32 = 16.0;
+
+#[derive(Debug, Clone, Deserialize)]
+pub enum AttackKind {
+    // Hits the target after winding up, if it's still in range by then.
+    Melee { windup_seconds: f32 },
+    // Shoots a fireball at the target while it's in sight.
+    Ranged { projectile_speed: f32 },
+}
+
+// The attack an enemy uses while in the `Attack` state. The range comes from `EnemyAiConfig`.
+#[derive(Debug, Clone, Component, Deserialize)]
+#[serde(default)]
+pub struct EnemyAttack {
+    pub kind: AttackKind,
+    // The base of the enemy's `AttackDamage` stat.
+    pub damage: f32,
+    pub cooldown_seconds: f32,
+}
+
+impl Default for EnemyAttack {
+    fn default() -> Self {
+        Self {
+            kind: AttackKind::Melee {
+                windup_seconds: 0.4,
+            },
+            damage: 5.0,
+            cooldown_seconds: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct AttackTimers {
+    pub windup: Option<Timer>,
+    pub cooldown: Timer,
+}
+
+impl AttackTimers {
+    // The first attack doesn't wait for the cooldown.
+    pub fn new(attack: &EnemyAttack) -> Self {
+        let mut cooldown = Timer::from_seconds(attack.cooldown_seconds, TimerMode::Once);
+        cooldown.set_elapsed(cooldown.duration());
+
+        Self {
+            windup: None,
+            cooldown,
+        }
+    }
+}
+
+fn enemy_attacks(
+    mut commands: Commands,
+    mut enemy_query: Query<
+        (
+            Entity,
+            &Transform,
+            &EnemyAi,
+            &EnemyAiConfig,
+            &EnemyAttack,
+            &mut AttackTimers,
+            &AttackDamage,
+            &StatusEffects,
+        ),
+        With<Enemy>,
+    >,
+    target_query: Query<&Transform, (With<Health>, Without<Enemy>)>,
+    wall_query: Query<Entity, With<Wall>>,
+    rapier_context: Res<RapierContext>,
+    fire_sprite_sheet: Res<FireSpriteSheet>,
+    time: Res<Time>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for (enemy, transform, ai, config, attack, mut timers, attack_damage, status_effects) in
+        enemy_query.iter_mut()
+    {
+        let timers = &mut *timers;
+        timers.cooldown.tick(time.delta());
+
+        // Leaving the attack state or getting stunned cancels the windup.
+        let EnemyState::Attack(target) = ai.state else {
+            timers.windup = None;
+            continue;
+        };
+        if status_effects.is_stunned() {
+            timers.windup = None;
+            continue;
+        }
+        let Ok(target_transform) = target_query.get(target) else {
+            continue;
+        };
+
+        let position = transform.translation.truncate();
+        let target_position = target_transform.translation.truncate();
+
+        match attack.kind {
+            AttackKind::Melee { windup_seconds } => match &mut timers.windup {
+                None if timers.cooldown.finished() => {
+                    timers.windup = Some(Timer::from_seconds(windup_seconds, TimerMode::Once));
+                }
+                Some(windup) => {
+                    if !windup.tick(time.delta()).finished() {
+                        continue;
+                    }
+
+                    // The target can step out of reach during the windup.
+                    if position.distance(target_position)
+                        <= config.attack_range * ATTACK_RANGE_SLACK
+                    {
+                        damage_events.send(DamageEvent::new(
+                            Some(enemy),
+                            target,
+                            attack_damage.0,
+                            DamageKind::Physical,
+                        ));
+                    }
+
+                    timers.windup = None;
+                    timers.cooldown.reset();
+                }
+                None => (),
+            },
+            AttackKind::Ranged { projectile_speed } => {
+                if !timers.cooldown.finished()
+                    || !has_line_of_sight(&rapier_context, &wall_query, position, target_position)
+                {
+                    continue;
+                }
+
+                let direction = (target_position - position).normalize_or_zero();
+                create_fireball(
+                    &mut commands,
+                    &fire_sprite_sheet,
+                    position + direction * PROJECTILE_SPAWN_DISTANCE,
+                    direction * projectile_speed,
+                    attack_damage.0,
+                    enemy,
+                );
+
+                timers.cooldown.reset();
+            }
+        }
+    }
+}
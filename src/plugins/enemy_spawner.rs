@@ -0,0 +1,353 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_rapier2d::prelude::RapierContext;
+use rand::Rng;
+
+use crate::tiled::{TiledObject, TiledObjectAppExt, Wall};
+
+use super::{
+    enemy::{spawn_enemy_of_kind, EnemyKind},
+    enemy_ai::{has_line_of_sight, EnemyGroup},
+    navigation::NavGrid,
+};
+
+pub struct EnemySpawnerPlugin;
+
+impl Plugin for EnemySpawnerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnerCleared>()
+            .register_tiled_object("EnemySpawner", spawn_enemy_spawner)
+            .add_system(init_spawners)
+            .add_system(update_spawners.after(init_spawners));
+    }
+}
+
+// How many random points are tried before spawning on the spawner itself.
+const SPAWN_ATTEMPTS: usize = 8;
+
+// Keeps enemies alive around its position. Without waves, it keeps `max_alive` enemies of `kind`
+// around, replacing each one `respawn_seconds` after it dies. With waves, it spawns them one after
+// the other, starting the next wave `respawn_seconds` after the previous one is defeated, and
+// sends `SpawnerCleared` once the last one is. Spawners are placed as `EnemySpawner` objects in
+// Tiled, or spawned from code along with a `SpatialBundle`.
+#[derive(Debug, Clone, Component)]
+pub struct EnemySpawner {
+    pub kind: String,
+    pub group: EnemyGroup,
+    pub max_alive: usize,
+    pub respawn_seconds: f32,
+    // Enemies appear at a random point within this distance that isn't behind a wall.
+    pub radius: f32,
+    pub waves: Vec<SpawnWave>,
+}
+
+impl Default for EnemySpawner {
+    fn default() -> Self {
+        Self {
+            kind: "slime".to_string(),
+            group: EnemyGroup::default(),
+            max_alive: 3,
+            respawn_seconds: 10.0,
+            radius: 32.0,
+            waves: Vec::new(),
+        }
+    }
+}
+
+// The kinds of enemies in a wave and how many of each. Only `max_alive` of them are out at once.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpawnWave {
+    pub enemies: Vec<(String, usize)>,
+}
+
+impl SpawnWave {
+    // Parses waves written as `slime:3, skeleton:1; slime:5`, with waves separated by `;`. A kind
+    // without a count spawns once. Kinds aren't checked here, they're loaded when they spawn.
+    pub fn parse_list(waves: &str) -> Result<Vec<Self>, String> {
+        waves
+            .split(';')
+            .filter(|wave| !wave.trim().is_empty())
+            .map(|wave| {
+                let enemies = wave
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|entry| !entry.is_empty())
+                    .map(|entry| {
+                        let (kind, count) = match entry.split_once(':') {
+                            Some((kind, count)) => (
+                                kind.trim(),
+                                count
+                                    .trim()
+                                    .parse()
+                                    .map_err(|_| format!("Invalid enemy count in `{entry}`"))?,
+                            ),
+                            None => (entry, 1),
+                        };
+                        if kind.is_empty() {
+                            return Err(format!("Missing enemy kind in `{entry}`"));
+                        }
+
+                        Ok((kind.to_string(), count))
+                    })
+                    .collect::<Result<_, _>>()?;
+
+                Ok(Self { enemies })
+            })
+            .collect()
+    }
+}
+
+// Sent when a spawner with waves has no enemies left to spawn.
+#[derive(Debug, Clone, Copy)]
+pub struct SpawnerCleared {
+    pub spawner: Entity,
+}
+
+// The progress of a spawner, added along with it.
+#[derive(Debug, Component)]
+pub struct SpawnerState {
+    alive: Vec<Entity>,
+    respawns: Vec<Timer>,
+    // The enemies of the current wave that haven't spawned yet.
+    queue: Vec<String>,
+    next_wave: usize,
+    wave_timer: Timer,
+    cleared: bool,
+}
+
+impl SpawnerState {
+    fn new(spawner: &EnemySpawner) -> Self {
+        // The first wave doesn't wait.
+        let mut wave_timer = Timer::from_seconds(spawner.respawn_seconds, TimerMode::Once);
+        wave_timer.set_elapsed(wave_timer.duration());
+
+        Self {
+            alive: Vec::new(),
+            respawns: Vec::new(),
+            queue: Vec::new(),
+            next_wave: 0,
+            wave_timer,
+            cleared: false,
+        }
+    }
+}
+
+fn spawn_enemy_spawner(entity: &mut EntityCommands, object: &TiledObject) {
+    let mut spawner = EnemySpawner::default();
+
+    if let Some(tiled::PropertyValue::StringValue(kind)) = object.get_property("kind") {
+        spawner.kind = kind.clone();
+    }
+    if let Some(tiled::PropertyValue::StringValue(group)) = object.get_property("group") {
+        spawner.group = EnemyGroup(group.clone());
+    }
+    if let Some(tiled::PropertyValue::IntValue(max_alive)) = object.get_property("max_alive") {
+        spawner.max_alive = (*max_alive).max(0) as usize;
+    }
+    match object.get_property("respawn_seconds") {
+        Some(tiled::PropertyValue::FloatValue(seconds)) => spawner.respawn_seconds = *seconds,
+        Some(tiled::PropertyValue::IntValue(seconds)) => spawner.respawn_seconds = *seconds as f32,
+        _ => (),
+    }
+    match object.get_property("radius") {
+        Some(tiled::PropertyValue::FloatValue(radius)) => spawner.radius = *radius,
+        Some(tiled::PropertyValue::IntValue(radius)) => spawner.radius = *radius as f32,
+        _ => (),
+    }
+    if let Some(tiled::PropertyValue::StringValue(waves)) = object.get_property("waves") {
+        match SpawnWave::parse_list(waves) {
+            Ok(waves) => spawner.waves = waves,
+            Err(error) => warn!("EnemySpawner {} has invalid waves: {error}", object.id),
+        }
+    }
+
+    entity.insert(spawner);
+}
+
+fn init_spawners(
+    mut commands: Commands,
+    spawner_query: Query<(Entity, &EnemySpawner), Added<EnemySpawner>>,
+) {
+    for (entity, spawner) in spawner_query.iter() {
+        // Such a spawner would never spawn its waves, nor be cleared.
+        if spawner.max_alive == 0 && !spawner.waves.is_empty() {
+            warn!("EnemySpawner {entity:?} has waves but a `max_alive` of 0, it won't spawn.");
+            continue;
+        }
+
+        commands.entity(entity).insert(SpawnerState::new(spawner));
+    }
+}
+
+fn update_spawners(
+    mut commands: Commands,
+    mut spawner_query: Query<(Entity, &EnemySpawner, &mut SpawnerState, &GlobalTransform)>,
+    enemy_query: Query<(), With<EnemyKind>>,
+    wall_query: Query<Entity, With<Wall>>,
+    nav_grid: Option<Res<NavGrid>>,
+    rapier_context: Res<RapierContext>,
+    asset_server: Res<AssetServer>,
+    time: Res<Time>,
+    mut cleared_events: EventWriter<SpawnerCleared>,
+) {
+    for (entity, spawner, mut state, transform) in spawner_query.iter_mut() {
+        let state = &mut *state;
+        let origin = transform.translation().truncate();
+
+        // Forget the enemies that died.
+        let alive_before = state.alive.len();
+        state.alive.retain(|&enemy| enemy_query.contains(enemy));
+        let died = alive_before - state.alive.len();
+
+        let mut kinds = Vec::new();
+        if spawner.waves.is_empty() {
+            state.respawns.extend(
+                (0..died).map(|_| Timer::from_seconds(spawner.respawn_seconds, TimerMode::Once)),
+            );
+            for respawn in state.respawns.iter_mut() {
+                respawn.tick(time.delta());
+            }
+            state.respawns.retain(|respawn| !respawn.finished());
+
+            let missing = spawner
+                .max_alive
+                .saturating_sub(state.alive.len() + state.respawns.len());
+            kinds.extend((0..missing).map(|_| spawner.kind.clone()));
+        } else {
+            if state.cleared {
+                continue;
+            }
+
+            if state.queue.is_empty() && state.alive.is_empty() {
+                if state.next_wave == spawner.waves.len() {
+                    state.cleared = true;
+                    cleared_events.send(SpawnerCleared { spawner: entity });
+                    continue;
+                }
+
+                if !state.wave_timer.tick(time.delta()).finished() {
+                    continue;
+                }
+
+                let wave = &spawner.waves[state.next_wave];
+                state.queue = wave
+                    .enemies
+                    .iter()
+                    .flat_map(|(kind, count)| std::iter::repeat_n(kind.clone(), *count))
+                    .rev()
+                    .collect();
+                state.next_wave += 1;
+                state.wave_timer.reset();
+            }
+
+            let free = spawner.max_alive.saturating_sub(state.alive.len());
+            let count = free.min(state.queue.len());
+            kinds.extend(state.queue.drain(state.queue.len() - count..).rev());
+        }
+
+        for kind in kinds {
+            let position = find_spawn_position(
+                origin,
+                spawner.radius,
+                nav_grid.as_deref(),
+                &rapier_context,
+                &wall_query,
+            );
+            let enemy = spawn_enemy_of_kind(
+                &mut commands,
+                &asset_server,
+                &kind,
+                position,
+                spawner.group.clone(),
+            );
+            state.alive.push(enemy);
+        }
+    }
+}
+
+// Picks a random point around the spawner that is walkable and can be reached in a straight line,
+// so enemies don't appear inside or behind walls.
+fn find_spawn_position(
+    origin: Vec2,
+    radius: f32,
+    nav_grid: Option<&NavGrid>,
+    rapier_context: &RapierContext,
+    wall_query: &Query<Entity, With<Wall>>,
+) -> Vec2 {
+    let mut rng = rand::thread_rng();
+
+    for _ in 0..SPAWN_ATTEMPTS {
+        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+        let distance = radius * rng.gen::<f32>().sqrt();
+        let position = origin + Vec2::from_angle(angle) * distance;
+
+        let walkable =
+            nav_grid.is_none_or(|nav_grid| nav_grid.is_walkable(nav_grid.world_to_cell(position)));
+        if walkable && has_line_of_sight(rapier_context, wall_query, origin, position) {
+            return position;
+        }
+    }
+
+    origin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wave(enemies: &[(&str, usize)]) -> SpawnWave {
+        SpawnWave {
+            enemies: enemies
+                .iter()
+                .map(|(kind, count)| (kind.to_string(), *count))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn parses_waves() {
+        let waves = SpawnWave::parse_list("slime:3, skeleton:1; slime : 5; skeleton").unwrap();
+
+        assert_eq!(
+            waves,
+            vec![
+                wave(&[("slime", 3), ("skeleton", 1)]),
+                wave(&[("slime", 5)]),
+                wave(&[("skeleton", 1)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_counts() {
+        for waves in ["slime:", "slime:-1", "slime:two", "slime:1.5", "slime:1:2"] {
+            let error = SpawnWave::parse_list(waves).unwrap_err();
+
+            assert!(
+                error.contains("Invalid enemy count"),
+                "{waves} gave: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_kinds() {
+        let waves = SpawnWave::parse_list("dragon:2").unwrap();
+
+        assert_eq!(waves, vec![wave(&[("dragon", 2)])]);
+    }
+
+    #[test]
+    fn skips_empty_entries() {
+        let waves = SpawnWave::parse_list(";slime:2,, skeleton,;;  ;").unwrap();
+
+        assert_eq!(waves, vec![wave(&[("slime", 2), ("skeleton", 1)])]);
+        assert!(SpawnWave::parse_list("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_counts_without_kind() {
+        let error = SpawnWave::parse_list("slime:1, :3").unwrap_err();
+
+        assert!(error.contains("Missing enemy kind"), "{error}");
+    }
+}
@@ -0,0 +1,201 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    character_stats::{Stat, StatModifier, Stats},
+    enemy::EnemyKind,
+    enemy_archetype::EnemyArchetype,
+    health::DeathEvent,
+    player::Player,
+};
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelUp>()
+            .add_event::<AllocateStatPoint>()
+            .add_system(allocation_keys)
+            .add_system(allocate_stat_points.after(allocation_keys))
+            .add_system_to_stage(CoreStage::PostUpdate, award_experience)
+            .add_system_to_stage(CoreStage::PostUpdate, level_up.after(award_experience));
+    }
+}
+
+// The experience needed to reach level 2, later levels need more.
+const BASE_LEVEL_EXPERIENCE: f32 = 50.0;
+const LEVEL_CURVE_EXPONENT: f32 = 1.5;
+const STAT_POINTS_PER_LEVEL: u32 = 3;
+
+// Tags the modifiers added to `Stats`, so they can be replaced when the level or points change.
+const LEVEL_MODIFIER_SOURCE: &str = "level";
+const STAT_POINTS_MODIFIER_SOURCE: &str = "stat_points";
+// The stats every level adds on its own, on top of the allocated points.
+const MAX_HEALTH_PER_LEVEL: f32 = 10.0;
+const ATTACK_DAMAGE_PER_LEVEL: f32 = 1.0;
+
+// The experience needed to go from `level` to the next one.
+pub fn experience_to_next_level(level: u32) -> u32 {
+    (BASE_LEVEL_EXPERIENCE * (level as f32).powf(LEVEL_CURVE_EXPONENT)).round() as u32
+}
+
+// What one point allocated to a stat adds to it.
+pub fn stat_point_bonus(stat: Stat) -> f32 {
+    match stat {
+        Stat::MaxHealth => 5.0,
+        Stat::WalkSpeed => 2.0,
+        Stat::SpellSpeed => 10.0,
+        Stat::AttackDamage => 1.0,
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct Experience {
+    pub level: u32,
+    // The experience gathered towards the next level.
+    pub experience: u32,
+    pub unspent_points: u32,
+    pub allocated_points: HashMap<Stat, u32>,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self {
+            level: 1,
+            experience: 0,
+            unspent_points: 0,
+            allocated_points: HashMap::default(),
+        }
+    }
+}
+
+impl Experience {
+    // Replaces the modifiers from levels and allocated points on `stats`.
+    fn apply_to(&self, stats: &mut Stats) {
+        stats.remove_modifiers(LEVEL_MODIFIER_SOURCE);
+        stats.remove_modifiers(STAT_POINTS_MODIFIER_SOURCE);
+
+        let levels_gained = (self.level - 1) as f32;
+        stats.add_modifier(StatModifier::flat(
+            Stat::MaxHealth,
+            MAX_HEALTH_PER_LEVEL * levels_gained,
+            LEVEL_MODIFIER_SOURCE,
+        ));
+        stats.add_modifier(StatModifier::flat(
+            Stat::AttackDamage,
+            ATTACK_DAMAGE_PER_LEVEL * levels_gained,
+            LEVEL_MODIFIER_SOURCE,
+        ));
+
+        for (&stat, &points) in self.allocated_points.iter() {
+            stats.add_modifier(StatModifier::flat(
+                stat,
+                stat_point_bonus(stat) * points as f32,
+                STAT_POINTS_MODIFIER_SOURCE,
+            ));
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LevelUp {
+    pub entity: Entity,
+    pub level: u32,
+}
+
+// Spends one of the character's unspent stat points on `stat`. Ignored without unspent points.
+#[derive(Debug, Clone, Copy)]
+pub struct AllocateStatPoint {
+    pub entity: Entity,
+    pub stat: Stat,
+}
+
+// Characters gain the experience of the enemies they kill, set by the enemy's archetype.
+fn award_experience(
+    mut death_events: EventReader<DeathEvent>,
+    mut experience_query: Query<&mut Experience>,
+    enemy_query: Query<&EnemyKind>,
+    archetypes: Res<Assets<EnemyArchetype>>,
+) {
+    for death in death_events.iter() {
+        let Some(killer) = death.killer else {
+            continue;
+        };
+        let Ok(mut experience) = experience_query.get_mut(killer) else {
+            continue;
+        };
+        let Some(archetype) = enemy_query
+            .get(death.entity)
+            .ok()
+            .and_then(|kind| archetypes.get(&kind.0))
+        else {
+            continue;
+        };
+
+        experience.experience += archetype.experience;
+    }
+}
+
+fn level_up(
+    mut experience_query: Query<(Entity, &mut Experience, &mut Stats), Changed<Experience>>,
+    mut level_up_events: EventWriter<LevelUp>,
+) {
+    for (entity, mut experience, mut stats) in experience_query.iter_mut() {
+        let mut leveled_up = false;
+        while experience.experience >= experience_to_next_level(experience.level) {
+            experience.experience -= experience_to_next_level(experience.level);
+            experience.level += 1;
+            experience.unspent_points += STAT_POINTS_PER_LEVEL;
+            leveled_up = true;
+
+            info!("Reached level {}", experience.level);
+            level_up_events.send(LevelUp {
+                entity,
+                level: experience.level,
+            });
+        }
+
+        if leveled_up {
+            experience.apply_to(&mut stats);
+        }
+    }
+}
+
+fn allocate_stat_points(
+    mut allocate_events: EventReader<AllocateStatPoint>,
+    mut experience_query: Query<(&mut Experience, &mut Stats)>,
+) {
+    for event in allocate_events.iter() {
+        let Ok((mut experience, mut stats)) = experience_query.get_mut(event.entity) else {
+            continue;
+        };
+        if experience.unspent_points == 0 {
+            continue;
+        }
+
+        experience.unspent_points -= 1;
+        *experience.allocated_points.entry(event.stat).or_insert(0) += 1;
+        experience.apply_to(&mut stats);
+    }
+}
+
+// The number keys spend the player's stat points.
+fn allocation_keys(
+    player_query: Query<Entity, (With<Player>, With<Experience>)>,
+    keyboard: Res<Input<KeyCode>>,
+    mut allocate_events: EventWriter<AllocateStatPoint>,
+) {
+    let Some((_, stat)) = [
+        (KeyCode::Key1, Stat::MaxHealth),
+        (KeyCode::Key2, Stat::WalkSpeed),
+        (KeyCode::Key3, Stat::SpellSpeed),
+        (KeyCode::Key4, Stat::AttackDamage),
+    ]
+    .into_iter()
+    .find(|(key, _)| keyboard.just_pressed(*key)) else {
+        return;
+    };
+
+    for entity in player_query.iter() {
+        allocate_events.send(AllocateStatPoint { entity, stat });
+    }
+}
@@ -1,5 +1,10 @@
-use super::character_stats::{Health, MaxHealth};
-use bevy::prelude::*;
+use super::character_stats::{
+    mitigate_damage, Armor, CriticalHits, DamageKind, Health, InvulnerabilityFrames, MaxHealth,
+    Resistances, Shield,
+};
+use super::player::Player;
+use bevy::{prelude::*, utils::HashSet};
+use rand::Rng;
 
 pub struct HealthPlugin;
 pub const TILE_SIZE: f32 = 16.0;
@@ -7,6 +12,23 @@ pub const TILE_SIZE: f32 = 16.0;
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_spritesheet)
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<HealEvent>()
+            .init_resource::<PendingDamage>()
+            .add_stage_after(CoreStage::Update, DamageStage, SystemStage::parallel())
+            .add_system_to_stage(DamageStage, collect_damage)
+            .add_system_to_stage(DamageStage, apply_resistances.after(collect_damage))
+            .add_system_to_stage(DamageStage, apply_critical_hits.after(apply_resistances))
+            .add_system_to_stage(
+                DamageStage,
+                apply_invulnerability.after(apply_critical_hits),
+            )
+            .add_system_to_stage(DamageStage, apply_shields.after(apply_invulnerability))
+            .add_system_to_stage(DamageStage, apply_damage.after(apply_shields))
+            .add_system_to_stage(DamageStage, apply_healing.after(apply_damage))
+            .add_system_to_stage(CoreStage::Last, despawn_dead)
+            .add_system(tick_invulnerability)
             .add_system(handle_bars);
         // .add_startup_system_to_stage(StartupStage::PostStartup, spawn_health);
         // .add_startup_system(spawn_dungeon_player)
@@ -19,6 +41,225 @@ impl Plugin for HealthPlugin {
     }
 }
 
+// Damage goes through a pipeline instead of changing `Health` directly, so everything that
+// mitigates it gets a say. Gameplay systems send `DamageEvent`s during `CoreStage::Update`, and the
+// `DamageStage` that follows resolves them in order:
+// - resistances and armor of the target
+// - critical hits of the source
+// - invulnerability frames of the target
+// - shields of the target
+// - health, sending a `DeathEvent` for every character it kills
+// Healing is sent as `HealEvent`s too, and applied after the damage of the same frame, so it can't
+// bring back a character that just died or go over its `MaxHealth`.
+// Plugins reacting to deaths (loot, experience, sounds) read `DeathEvent`s in
+// `CoreStage::PostUpdate`, while the dead character still exists. It's despawned in
+// `CoreStage::Last`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, StageLabel)]
+pub struct DamageStage;
+
+#[derive(Debug, Clone, Copy)]
+pub struct DamageEvent {
+    // The character that caused the damage, if any.
+    pub source: Option<Entity>,
+    pub target: Entity,
+    pub amount: f32,
+    pub kind: DamageKind,
+    // Damage over time, like terrain or poison, ticks every frame. It can't be critical and
+    // ignores invulnerability frames, without starting them.
+    pub over_time: bool,
+}
+
+impl DamageEvent {
+    pub fn new(source: Option<Entity>, target: Entity, amount: f32, kind: DamageKind) -> Self {
+        Self {
+            source,
+            target,
+            amount,
+            kind,
+            over_time: false,
+        }
+    }
+
+    pub fn over_time(mut self) -> Self {
+        self.over_time = true;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    // The source of the damage that killed the character.
+    pub killer: Option<Entity>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HealEvent {
+    pub target: Entity,
+    pub amount: f32,
+}
+
+// The damage being resolved by the `DamageStage` this frame.
+#[derive(Debug, Default, Resource)]
+pub struct PendingDamage(pub Vec<DamageEvent>);
+
+// Set on characters with `InvulnerabilityFrames` after they get hit.
+#[derive(Debug, Component)]
+pub struct Invulnerable(pub Timer);
+
+fn collect_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    mut pending_damage: ResMut<PendingDamage>,
+) {
+    pending_damage.0.clear();
+    pending_damage.0.extend(damage_events.iter().copied());
+}
+
+fn apply_resistances(
+    mut pending_damage: ResMut<PendingDamage>,
+    resistance_query: Query<(Option<&Resistances>, Option<&Armor>)>,
+) {
+    for damage in pending_damage.0.iter_mut() {
+        if let Ok((resistances, armor)) = resistance_query.get(damage.target) {
+            damage.amount = mitigate_damage(damage.amount, damage.kind, resistances, armor);
+        }
+    }
+}
+
+fn apply_critical_hits(
+    mut pending_damage: ResMut<PendingDamage>,
+    critical_query: Query<&CriticalHits>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for damage in pending_damage.0.iter_mut() {
+        if damage.over_time {
+            continue;
+        }
+
+        let Some(critical_hits) = damage
+            .source
+            .and_then(|source| critical_query.get(source).ok())
+        else {
+            continue;
+        };
+
+        if rng.gen::<f32>() < critical_hits.chance {
+            damage.amount *= critical_hits.multiplier;
+        }
+    }
+}
+
+fn apply_invulnerability(
+    mut commands: Commands,
+    mut pending_damage: ResMut<PendingDamage>,
+    frames_query: Query<(&InvulnerabilityFrames, Option<&Invulnerable>)>,
+) {
+    // Characters hit this frame, whose `Invulnerable` isn't inserted yet.
+    let mut hit = HashSet::default();
+
+    for damage in pending_damage.0.iter_mut() {
+        if damage.over_time || damage.amount <= 0.0 {
+            continue;
+        }
+        let Ok((frames, invulnerable)) = frames_query.get(damage.target) else {
+            continue;
+        };
+
+        let invulnerable = invulnerable.is_some_and(|invulnerable| !invulnerable.0.finished());
+        if invulnerable || !hit.insert(damage.target) {
+            damage.amount = 0.0;
+            continue;
+        }
+
+        commands
+            .entity(damage.target)
+            .insert(Invulnerable(Timer::from_seconds(
+                frames.seconds,
+                TimerMode::Once,
+            )));
+    }
+}
+
+fn apply_shields(mut pending_damage: ResMut<PendingDamage>, mut shield_query: Query<&mut Shield>) {
+    for damage in pending_damage.0.iter_mut() {
+        let Ok(mut shield) = shield_query.get_mut(damage.target) else {
+            continue;
+        };
+
+        let absorbed = damage.amount.min(shield.0).max(0.0);
+        shield.0 -= absorbed;
+        damage.amount -= absorbed;
+    }
+}
+
+fn apply_damage(
+    pending_damage: Res<PendingDamage>,
+    mut health_query: Query<&mut Health>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for damage in pending_damage.0.iter() {
+        let Ok(mut health) = health_query.get_mut(damage.target) else {
+            continue;
+        };
+
+        // Only the hit that brings health to 0 kills.
+        let was_alive = health.0 > 0.0;
+        health.0 -= damage.amount;
+        if was_alive && health.0 <= 0.0 {
+            death_events.send(DeathEvent {
+                entity: damage.target,
+                killer: damage.source,
+            });
+        }
+    }
+}
+
+fn apply_healing(
+    mut heal_events: EventReader<HealEvent>,
+    mut health_query: Query<(&mut Health, Option<&MaxHealth>)>,
+) {
+    for heal in heal_events.iter() {
+        let Ok((mut health, max_health)) = health_query.get_mut(heal.target) else {
+            continue;
+        };
+        if health.0 <= 0.0 {
+            continue;
+        }
+
+        let max = max_health.map_or(f32::INFINITY, |max_health| max_health.0);
+        health.0 = (health.0 + heal.amount).min(max);
+    }
+}
+
+// The player is respawned by `PlayerPlugin` instead.
+fn despawn_dead(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    player_query: Query<(), With<Player>>,
+) {
+    for death in death_events.iter() {
+        if player_query.contains(death.entity) {
+            continue;
+        }
+        if let Some(entity) = commands.get_entity(death.entity) {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+fn tick_invulnerability(
+    mut commands: Commands,
+    mut invulnerable_query: Query<(Entity, &mut Invulnerable)>,
+    time: Res<Time>,
+) {
+    for (entity, mut invulnerable) in invulnerable_query.iter_mut() {
+        if invulnerable.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
 #[derive(Debug, Resource)]
 pub struct HealthSpriteSheet(Handle<TextureAtlas>);
 
@@ -42,25 +283,24 @@ pub struct HealthBar {
 #[derive(Debug, Component)]
 pub struct Bar(pub usize);
 
+// Hides the bars above the character's health, and shows them again once it heals.
 pub fn handle_bars(
-    mut commands: Commands,
-    healthbar_query: Query<&Bar>,
-    character_query: Query<(Entity, &MaxHealth, &Health, &Children)>,
+    mut bar_query: Query<&mut Visibility, With<Bar>>,
+    character_query: Query<(&MaxHealth, &Health, &Children)>,
 ) {
-    for (_, max_health, health, children) in character_query.iter() {
+    for (max_health, health, children) in character_query.iter() {
         let bars = children
-            .into_iter()
-            .filter(|c| healthbar_query.get(**c).is_ok())
+            .iter()
+            .filter(|child| bar_query.contains(**child))
+            .copied()
             .collect::<Vec<_>>();
         let chunk = max_health.0 / 23.0;
+        // Bars are numbered from 1, the first one stays until the character dies.
+        let shown = ((health.0 / chunk).round() as usize).max(1);
 
-        if max_health.0 != health.0 {
-            for i in (health.0 / chunk).round() as usize..23 {
-                if bars.get(i - 1).is_some() {
-                    if commands.get_entity(*bars[i - 1]).is_some() {
-                        commands.entity(*bars[i - 1]).despawn();
-                    }
-                }
+        for (i, bar) in (1..).zip(bars) {
+            if let Ok(mut visibility) = bar_query.get_mut(bar) {
+                visibility.is_visible = !(shown..23).contains(&i);
             }
         }
     }
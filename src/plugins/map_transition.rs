@@ -0,0 +1,189 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_ecs_tilemap::prelude::TileStorage;
+
+use crate::tiled::{
+    despawn_layers, TiledLayersStorage, TiledMap, TiledMapBundle, TiledObject, TiledObjectAppExt,
+};
+
+use super::player::Player;
+
+pub struct MapTransitionPlugin;
+
+impl Plugin for MapTransitionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurrentMap>()
+            .add_event::<ChangeMap>()
+            .register_tiled_object("Portal", spawn_portal)
+            .add_system(enter_portals)
+            .add_system(change_map)
+            .add_system(despawn_scoped_on_reload);
+    }
+}
+
+// Tracks the map that is currently loaded and the spawn point the player should be placed at.
+#[derive(Debug, Default, Resource)]
+pub struct CurrentMap {
+    pub handle: Option<Handle<TiledMap>>,
+    pub entity: Option<Entity>,
+    pub spawn_point: Option<String>,
+}
+
+// Requests the current map to be replaced with the map at the given asset path.
+#[derive(Debug, Clone)]
+pub struct ChangeMap {
+    pub map: String,
+    pub spawn_point: Option<String>,
+}
+
+impl ChangeMap {
+    pub fn new(map: impl Into<String>) -> Self {
+        Self {
+            map: map.into(),
+            spawn_point: None,
+        }
+    }
+}
+
+// Entities that only belong to the current map, like enemies and projectiles.
+#[derive(Debug, Component)]
+pub struct MapScoped;
+
+// A region that moves the player to another map. Portals are placed as `Portal` objects in Tiled
+// with a `map` property naming the target TMX file and an optional `spawn` property naming the
+// `PlayerSpawn` object to arrive at.
+#[derive(Debug, Component)]
+pub struct Portal {
+    pub map: String,
+    pub spawn_point: Option<String>,
+    pub half_size: Vec2,
+}
+
+fn spawn_portal(entity: &mut EntityCommands, object: &TiledObject) {
+    let map = match object.get_property("map") {
+        Some(tiled::PropertyValue::StringValue(map) | tiled::PropertyValue::FileValue(map)) => {
+            map.clone()
+        }
+        _ => {
+            warn!("Portal {} has no `map` property.", object.id);
+            return;
+        }
+    };
+
+    let spawn_point = match object.get_property("spawn") {
+        Some(tiled::PropertyValue::StringValue(spawn_point)) => Some(spawn_point.clone()),
+        _ => None,
+    };
+
+    entity.insert(Portal {
+        map,
+        spawn_point,
+        half_size: object.size / 2.0,
+    });
+}
+
+fn enter_portals(
+    portal_query: Query<(&Portal, &GlobalTransform)>,
+    new_portal_query: Query<(), Added<Portal>>,
+    player_query: Query<&Transform, With<Player>>,
+    mut change_map_events: EventWriter<ChangeMap>,
+    // Set while the player stands in the portal they took or arrived on, so portals only trigger
+    // when stepping into them and arriving on a portal doesn't send the player straight back.
+    mut in_portal: Local<bool>,
+) {
+    // Neither the portals of a map that was just spawned nor the player arriving on it are in
+    // place yet.
+    if !new_portal_query.is_empty() {
+        return;
+    }
+
+    for player_transform in player_query.iter() {
+        let player_position = player_transform.translation.truncate();
+
+        let entered_portal = portal_query.iter().find(|(portal, portal_transform)| {
+            (player_position - portal_transform.translation().truncate())
+                .abs()
+                .cmple(portal.half_size)
+                .all()
+        });
+
+        match entered_portal {
+            Some((portal, _)) if !*in_portal => {
+                *in_portal = true;
+                change_map_events.send(ChangeMap {
+                    map: portal.map.clone(),
+                    spawn_point: portal.spawn_point.clone(),
+                });
+            }
+            Some(_) => (),
+            // The next map is still loading.
+            None if portal_query.is_empty() => (),
+            None => *in_portal = false,
+        }
+    }
+}
+
+fn change_map(
+    mut commands: Commands,
+    mut change_map_events: EventReader<ChangeMap>,
+    mut current_map: ResMut<CurrentMap>,
+    asset_server: Res<AssetServer>,
+    mut map_query: Query<&mut TiledLayersStorage>,
+    tile_storage_query: Query<&TileStorage>,
+    scoped_query: Query<Entity, With<MapScoped>>,
+) {
+    let Some(event) = change_map_events.iter().last() else {
+        return;
+    };
+
+    if let Some(map_entity) = current_map.entity.take() {
+        if let Ok(mut layer_storage) = map_query.get_mut(map_entity) {
+            despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+        }
+        commands.entity(map_entity).despawn_recursive();
+
+        for scoped_entity in scoped_query.iter() {
+            commands.entity(scoped_entity).despawn_recursive();
+        }
+    }
+
+    info!("Loading map {}", event.map);
+    let map_handle: Handle<TiledMap> = asset_server.load(event.map.as_str());
+
+    let map_entity = commands
+        .spawn(TiledMapBundle {
+            tiled_map: map_handle.clone(),
+            transform: Transform {
+                scale: Vec3::new(1600.0, 900.0, 0.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .id();
+
+    current_map.handle = Some(map_handle);
+    current_map.entity = Some(map_entity);
+    current_map.spawn_point = event.spawn_point.clone();
+}
+
+// Reloading the current map respawns its objects, so the entities they spawned are despawned
+// along with the old layers instead of being duplicated.
+fn despawn_scoped_on_reload(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<TiledMap>>,
+    current_map: Res<CurrentMap>,
+    scoped_query: Query<Entity, With<MapScoped>>,
+) {
+    let reloaded = map_events.iter().any(|event| match event {
+        AssetEvent::Modified { handle } | AssetEvent::Removed { handle } => {
+            current_map.handle.as_ref() == Some(handle)
+        }
+        AssetEvent::Created { .. } => false,
+    });
+    if !reloaded {
+        return;
+    }
+
+    for scoped_entity in scoped_query.iter() {
+        commands.entity(scoped_entity).despawn_recursive();
+    }
+}
@@ -1,11 +1,33 @@
+mod boss;
 mod character_stats;
+mod chest;
 mod enemy;
+mod enemy_ai;
+mod enemy_archetype;
+mod enemy_attack;
+mod enemy_spawner;
+mod experience;
 mod health;
+mod map_transition;
+mod navigation;
 mod player;
 mod skills;
+mod status_effects;
+mod terrain;
 mod utils;
 
+pub use boss::BossPlugin;
+pub use character_stats::CharacterStatsPlugin;
+pub use chest::ChestPlugin;
 pub use enemy::EnemyPlugin;
+pub use enemy_ai::EnemyAiPlugin;
+pub use enemy_attack::EnemyAttackPlugin;
+pub use enemy_spawner::EnemySpawnerPlugin;
+pub use experience::ExperiencePlugin;
 pub use health::HealthPlugin;
+pub use map_transition::{ChangeMap, MapTransitionPlugin};
+pub use navigation::NavigationPlugin;
 pub use player::PlayerPlugin;
 pub use skills::SkillsPlugin;
+pub use status_effects::StatusEffectsPlugin;
+pub use terrain::TerrainPlugin;
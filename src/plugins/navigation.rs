@@ -0,0 +1,401 @@
+use std::{cmp::Reverse, collections::BinaryHeap, time::Duration};
+
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::tiled::TiledMap;
+
+use super::map_transition::CurrentMap;
+
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(build_nav_grid);
+    }
+}
+
+// How often a path is recomputed while its target moves.
+const REPATH_SECONDS: f32 = 0.5;
+// How close a character has to get to a waypoint before walking to the next one.
+const WAYPOINT_ARRIVE_DISTANCE: f32 = 2.0;
+
+// The cost of moving to a neighbouring cell, scaled so diagonals stay integers.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// The walkable cells of the current map, built from its rigid tiles. Cells are counted from the
+// bottom left corner of the area covered by the map's tile layers, like the tilemaps.
+#[derive(Debug, Resource)]
+pub struct NavGrid {
+    pub width: u32,
+    pub height: u32,
+    pub cell_size: Vec2,
+    // The world position of the bottom left corner of the grid.
+    pub origin: Vec2,
+    solid: Vec<bool>,
+}
+
+impl NavGrid {
+    pub fn new(width: u32, height: u32, cell_size: Vec2, origin: Vec2, solid: Vec<bool>) -> Self {
+        assert_eq!(solid.len(), (width * height) as usize);
+
+        Self {
+            width,
+            height,
+            cell_size,
+            origin,
+            solid,
+        }
+    }
+
+    // Builds the grid of an orthogonal map. Infinite maps get a grid covering all of their chunks.
+    pub fn from_tiled_map(tiled_map: &TiledMap) -> Option<Self> {
+        if tiled_map.map.orientation != tiled::Orientation::Orthogonal {
+            return None;
+        }
+
+        let (region, solid) = tiled_map.solid_tiles();
+        let cell_size = tiled_map.grid.tile_size;
+        let bottom_left_tile = tiled_map
+            .grid
+            .tile_center(region.min_x, region.min_y + region.size.y as i32 - 1);
+
+        Some(Self::new(
+            region.size.x,
+            region.size.y,
+            cell_size,
+            bottom_left_tile - cell_size / 2.0,
+            solid,
+        ))
+    }
+
+    pub fn is_walkable(&self, cell: IVec2) -> bool {
+        cell.x >= 0
+            && cell.y >= 0
+            && (cell.x as u32) < self.width
+            && (cell.y as u32) < self.height
+            && !self.solid[(cell.y as u32 * self.width + cell.x as u32) as usize]
+    }
+
+    pub fn world_to_cell(&self, position: Vec2) -> IVec2 {
+        ((position - self.origin) / self.cell_size)
+            .floor()
+            .as_ivec2()
+    }
+
+    // Returns the center of the cell.
+    pub fn cell_to_world(&self, cell: IVec2) -> Vec2 {
+        self.origin + (cell.as_vec2() + Vec2::splat(0.5)) * self.cell_size
+    }
+
+    // Finds a path between two world positions, as the centers of the cells to walk through. The
+    // cell of `start` isn't included, so an empty path means both are in the same cell.
+    pub fn find_path(&self, start: Vec2, goal: Vec2) -> Option<Vec<Vec2>> {
+        let path = self.find_cell_path(self.world_to_cell(start), self.world_to_cell(goal))?;
+
+        Some(
+            path.into_iter()
+                .skip(1)
+                .map(|cell| self.cell_to_world(cell))
+                .collect(),
+        )
+    }
+
+    // A* over the grid with diagonal moves. Diagonals can't cut the corner of a solid cell, so
+    // characters don't get stuck on wall edges. The path includes both `start` and `goal`.
+    pub fn find_cell_path(&self, start: IVec2, goal: IVec2) -> Option<Vec<IVec2>> {
+        if !self.is_walkable(goal) {
+            return None;
+        }
+
+        let mut open = BinaryHeap::new();
+        let mut came_from = HashMap::<IVec2, IVec2>::default();
+        let mut costs = HashMap::<IVec2, u32>::default();
+
+        costs.insert(start, 0);
+        open.push(Reverse((heuristic(start, goal), 0, start.x, start.y)));
+
+        while let Some(Reverse((_, cost, x, y))) = open.pop() {
+            let cell = IVec2::new(x, y);
+            // A cheaper way to the cell was found after this entry was pushed.
+            if cost > costs[&cell] {
+                continue;
+            }
+
+            if cell == goal {
+                let mut path = vec![cell];
+                let mut current = cell;
+                while let Some(&previous) = came_from.get(&current) {
+                    path.push(previous);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for (neighbour, step_cost) in self.neighbours(cell) {
+                let neighbour_cost = cost + step_cost;
+                if costs
+                    .get(&neighbour)
+                    .is_none_or(|&known_cost| neighbour_cost < known_cost)
+                {
+                    costs.insert(neighbour, neighbour_cost);
+                    came_from.insert(neighbour, cell);
+                    open.push(Reverse((
+                        neighbour_cost + heuristic(neighbour, goal),
+                        neighbour_cost,
+                        neighbour.x,
+                        neighbour.y,
+                    )));
+                }
+            }
+        }
+
+        None
+    }
+
+    fn neighbours(&self, cell: IVec2) -> impl Iterator<Item = (IVec2, u32)> + '_ {
+        [
+            IVec2::new(1, 0),
+            IVec2::new(-1, 0),
+            IVec2::new(0, 1),
+            IVec2::new(0, -1),
+            IVec2::new(1, 1),
+            IVec2::new(1, -1),
+            IVec2::new(-1, 1),
+            IVec2::new(-1, -1),
+        ]
+        .into_iter()
+        .filter_map(move |direction| {
+            let neighbour = cell + direction;
+            if !self.is_walkable(neighbour) {
+                return None;
+            }
+
+            if direction.x != 0 && direction.y != 0 {
+                let cuts_corner = !self.is_walkable(cell + IVec2::new(direction.x, 0))
+                    || !self.is_walkable(cell + IVec2::new(0, direction.y));
+                if cuts_corner {
+                    return None;
+                }
+                return Some((neighbour, DIAGONAL_COST));
+            }
+
+            Some((neighbour, STRAIGHT_COST))
+        })
+    }
+}
+
+// The octile distance, which never overestimates the cost with diagonal moves.
+fn heuristic(cell: IVec2, goal: IVec2) -> u32 {
+    let delta = (goal - cell).abs();
+    let (low, high) = (delta.x.min(delta.y) as u32, delta.x.max(delta.y) as u32);
+
+    DIAGONAL_COST * low + STRAIGHT_COST * (high - low)
+}
+
+// The waypoints a character is walking through towards a goal.
+#[derive(Debug, Component)]
+pub struct NavPath {
+    pub goal: Option<Vec2>,
+    pub waypoints: Vec<Vec2>,
+    pub repath_timer: Timer,
+}
+
+impl Default for NavPath {
+    fn default() -> Self {
+        Self {
+            goal: None,
+            waypoints: Vec::new(),
+            repath_timer: Timer::from_seconds(REPATH_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+impl NavPath {
+    // Returns the point to walk towards to reach `goal`. The path is recomputed when the goal
+    // moves to another cell and every `REPATH_SECONDS`, in case the way got blocked. Without a
+    // grid, or once in the goal's cell, the goal itself is returned.
+    pub fn steer(
+        &mut self,
+        nav_grid: Option<&NavGrid>,
+        position: Vec2,
+        goal: Vec2,
+        delta: Duration,
+    ) -> Vec2 {
+        let Some(nav_grid) = nav_grid else {
+            return goal;
+        };
+
+        self.repath_timer.tick(delta);
+        let goal_moved = self.goal.is_none_or(|previous_goal| {
+            nav_grid.world_to_cell(previous_goal) != nav_grid.world_to_cell(goal)
+        });
+
+        if goal_moved || self.repath_timer.just_finished() {
+            self.goal = Some(goal);
+            self.waypoints = nav_grid.find_path(position, goal).unwrap_or_default();
+        }
+
+        self.next_waypoint(position).unwrap_or(goal)
+    }
+
+    // Returns the waypoint to walk towards, skipping the ones already reached.
+    fn next_waypoint(&mut self, position: Vec2) -> Option<Vec2> {
+        while let Some(&waypoint) = self.waypoints.first() {
+            if waypoint.distance(position) > WAYPOINT_ARRIVE_DISTANCE {
+                return Some(waypoint);
+            }
+            self.waypoints.remove(0);
+        }
+
+        None
+    }
+}
+
+fn build_nav_grid(
+    mut commands: Commands,
+    current_map: Res<CurrentMap>,
+    maps: Res<Assets<TiledMap>>,
+    mut map_events: EventReader<AssetEvent<TiledMap>>,
+    mut built_map: Local<Option<Handle<TiledMap>>>,
+) {
+    let Some(map_handle) = &current_map.handle else {
+        return;
+    };
+
+    // Rebuild when the map changes or is reloaded from disk.
+    let modified = map_events
+        .iter()
+        .any(|event| matches!(event, AssetEvent::Modified { handle } if handle == map_handle));
+    if built_map.as_ref() == Some(map_handle) && !modified {
+        return;
+    }
+
+    let Some(tiled_map) = maps.get(map_handle) else {
+        return;
+    };
+    *built_map = Some(map_handle.clone());
+
+    match NavGrid::from_tiled_map(tiled_map) {
+        Some(nav_grid) => commands.insert_resource(nav_grid),
+        None => {
+            warn!("Navigation is only supported on orthogonal maps.");
+            commands.remove_resource::<NavGrid>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{load_map, solid_grid};
+
+    // A grid of 16 pixel cells with its bottom left corner on the origin.
+    fn grid(rows: &[&str]) -> NavGrid {
+        let (solid, width, height) = solid_grid(rows);
+        NavGrid::new(width, height, Vec2::splat(16.0), Vec2::ZERO, solid)
+    }
+
+    fn path_cost(path: &[IVec2]) -> u32 {
+        path.windows(2)
+            .map(|step| match (step[1] - step[0]).abs() {
+                IVec2 { x: 1, y: 1 } => DIAGONAL_COST,
+                _ => STRAIGHT_COST,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn finds_straight_path() {
+        let nav_grid = grid(&["....."]);
+        let path = nav_grid.find_cell_path(IVec2::new(0, 0), IVec2::new(4, 0));
+
+        assert_eq!(
+            path,
+            Some((0..5).map(|x| IVec2::new(x, 0)).collect::<Vec<_>>())
+        );
+    }
+
+    #[test]
+    fn finds_path_around_wall() {
+        let nav_grid = grid(&[".....", "..#..", "..#..", "..#..", "....."]);
+        let path = nav_grid
+            .find_cell_path(IVec2::new(0, 2), IVec2::new(4, 2))
+            .unwrap();
+
+        assert_eq!(path.first(), Some(&IVec2::new(0, 2)));
+        assert_eq!(path.last(), Some(&IVec2::new(4, 2)));
+        assert!(path.iter().all(|&cell| nav_grid.is_walkable(cell)));
+        assert_eq!(path_cost(&path), 68);
+    }
+
+    #[test]
+    fn unreachable_goal_has_no_path() {
+        let nav_grid = grid(&[".....", ".###.", ".#.#.", ".###.", "....."]);
+
+        assert_eq!(
+            nav_grid.find_cell_path(IVec2::new(0, 0), IVec2::new(2, 2)),
+            None
+        );
+        assert_eq!(
+            nav_grid.find_cell_path(IVec2::new(0, 0), IVec2::new(1, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn start_at_goal_is_a_single_cell_path() {
+        let nav_grid = grid(&["...", "...", "..."]);
+        let cell = IVec2::new(1, 1);
+
+        assert_eq!(nav_grid.find_cell_path(cell, cell), Some(vec![cell]));
+        assert_eq!(
+            nav_grid.find_path(Vec2::new(20.0, 20.0), Vec2::new(28.0, 28.0)),
+            Some(Vec::new())
+        );
+    }
+
+    #[test]
+    fn diagonal_moves_do_not_cut_corners() {
+        let nav_grid = grid(&[".#", "#."]);
+
+        assert_eq!(
+            nav_grid.find_cell_path(IVec2::new(1, 0), IVec2::new(0, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn diagonal_moves_go_around_a_single_corner() {
+        let nav_grid = grid(&["..", "#."]);
+        let path = nav_grid
+            .find_cell_path(IVec2::new(1, 0), IVec2::new(0, 1))
+            .unwrap();
+
+        assert_eq!(
+            path,
+            vec![IVec2::new(1, 0), IVec2::new(1, 1), IVec2::new(0, 1)]
+        );
+    }
+
+    #[test]
+    fn covers_every_chunk_of_infinite_maps() {
+        let nav_grid = NavGrid::from_tiled_map(&load_map("tests/fixtures/infinite.tmx")).unwrap();
+
+        // The chunks span tiles -16 to 15 on both axes, and the map's own 4x3 tiles are centered on
+        // the origin.
+        assert_eq!((nav_grid.width, nav_grid.height), (32, 32));
+        assert_eq!(nav_grid.origin, Vec2::new(-288.0, -232.0));
+
+        // The solid tiles are the first tile of the top left chunk and the last one of the bottom
+        // right chunk.
+        assert!(!nav_grid.is_walkable(IVec2::new(0, 31)));
+        assert!(!nav_grid.is_walkable(IVec2::new(31, 0)));
+        assert!(nav_grid.is_walkable(IVec2::new(16, 15)));
+        assert_eq!(
+            nav_grid.world_to_cell(Vec2::new(216.0, -224.0)),
+            IVec2::new(31, 0)
+        );
+    }
+}
@@ -1,7 +1,20 @@
 use bevy::{prelude::*, render::camera::ScalingMode, sprite::Anchor};
-use bevy_rapier2d::prelude::{Collider, KinematicCharacterController, RigidBody};
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, KinematicCharacterController, RigidBody};
 
-use super::{skills::Cooldown, utils::AnimationTimer};
+use crate::tiled::{TiledObject, TiledObjectAppExt, YSort, Y_SORT_MAX_Z};
+
+use super::{
+    character_stats::{
+        CriticalHits, Health, InvulnerabilityFrames, MaxHealth, Stat, Stats, WalkSpeed,
+    },
+    experience::Experience,
+    health::{DeathEvent, Invulnerable},
+    map_transition::CurrentMap,
+    skills::Cooldown,
+    status_effects::StatusEffects,
+    terrain::Terrain,
+    utils::AnimationTimer,
+};
 
 pub struct PlayerPlugin;
 pub const TILE_SIZE: f32 = 16.0;
@@ -11,6 +24,12 @@ impl Plugin for PlayerPlugin {
         app.add_startup_system_to_stage(StartupStage::PreStartup, load_spritesheet)
             .add_startup_system(spawn_dungeon_player)
             // .add_startup_system(spawn_physics)
+            .register_tiled_object("PlayerSpawn", |entity, _| {
+                entity.insert(PlayerSpawn);
+            })
+            .add_event::<PlayerRespawned>()
+            .add_system(move_to_spawn_point)
+            .add_system_to_stage(CoreStage::PostUpdate, respawn_player)
             .add_system(player_movement)
             .add_system(animate_sprite)
             .add_system(handle_sprite_change)
@@ -30,6 +49,10 @@ fn spawn_camera() -> Camera2dBundle {
 
     camera.projection.scaling_mode = ScalingMode::None;
 
+    // The camera follows the player, whose z changes with y-sorting, so extend its depth to keep
+    // the map layers below the player in view.
+    camera.projection.far += Y_SORT_MAX_Z;
+
     camera
 }
 
@@ -70,11 +93,9 @@ pub enum FacingDirection {
 
 #[derive(Debug, Component)]
 pub struct Player {
-    speed: f32,
     pub facing_direction: FacingDirection,
     idle: bool,
     pub can_recast: bool,
-    pub spell_speed: f32,
 }
 
 fn handle_sprite_change(
@@ -110,22 +131,18 @@ fn spawn_dungeon_player(mut commands: Commands, dungeon_sheet: Res<PlayerDungeon
             SpriteSheetBundle {
                 sprite,
                 texture_atlas: dungeon_sheet.0.clone(),
-                transform: Transform {
-                    translation: Vec3::new(0.0, 0.0, 0.1),
-                    ..Default::default()
-                },
                 ..Default::default()
             },
             RigidBody::KinematicPositionBased,
             Collider::cuboid(TILE_SIZE - 9.0, TILE_SIZE - 2.0),
+            // Sorted by the feet, which are below the sprite's anchor.
+            YSort::new(-TILE_SIZE * 0.75),
         ))
         .insert(Name::new("Dungeon Player"))
         .insert(Player {
-            speed: 50.0,
             facing_direction: FacingDirection::Right,
             idle: true,
             can_recast: true,
-            spell_speed: 150.0,
         })
         .insert(AnimationTimer(Timer::from_seconds(
             0.1,
@@ -138,7 +155,109 @@ fn spawn_dungeon_player(mut commands: Commands, dungeon_sheet: Res<PlayerDungeon
             apply_impulse_to_dynamic_bodies: false,
             ..Default::default()
         })
-        .insert(Cooldown(Timer::from_seconds(2.0, TimerMode::Repeating)));
+        .insert(Cooldown(Timer::from_seconds(2.0, TimerMode::Repeating)))
+        .insert(Terrain::default())
+        .insert(StatusEffects::default())
+        // Lets enemy fireballs hit the player.
+        .insert(ActiveEvents::COLLISION_EVENTS)
+        .insert(Health(100.0))
+        .insert(Stats::new([
+            (Stat::MaxHealth, 100.0),
+            (Stat::WalkSpeed, 50.0),
+            (Stat::SpellSpeed, 150.0),
+            (Stat::AttackDamage, 10.0),
+        ]))
+        .insert(Experience::default())
+        .insert(InvulnerabilityFrames { seconds: 0.5 })
+        .insert(CriticalHits {
+            chance: 0.1,
+            multiplier: 2.0,
+        })
+        .insert(RespawnPoint(Vec2::ZERO));
+}
+
+#[derive(Debug, Component)]
+pub struct PlayerSpawn;
+
+// Where the player comes back after dying, the spawn point they entered the map at.
+#[derive(Debug, Component)]
+pub struct RespawnPoint(pub Vec2);
+
+// Sent when the player died and was brought back at their `RespawnPoint`.
+#[derive(Debug, Clone, Copy)]
+pub struct PlayerRespawned {
+    pub entity: Entity,
+}
+
+fn move_to_spawn_point(
+    spawn_query: Query<(&Transform, &TiledObject), Added<PlayerSpawn>>,
+    mut player_query: Query<
+        (&mut Transform, &mut RespawnPoint),
+        (With<Player>, Without<PlayerSpawn>),
+    >,
+    current_map: Res<CurrentMap>,
+) {
+    let spawns = spawn_query.iter().collect::<Vec<_>>();
+    if spawns.is_empty() {
+        return;
+    }
+
+    let named_spawn = current_map.spawn_point.as_ref().and_then(|spawn_point| {
+        spawns
+            .iter()
+            .find(|(_, spawn_object)| &spawn_object.name == spawn_point)
+    });
+    if let (Some(spawn_point), None) = (&current_map.spawn_point, named_spawn) {
+        warn!("The map has no spawn point named `{spawn_point}`, using its default spawn.");
+    }
+
+    // Without a named spawn point the player is placed at the map's default spawn, the first
+    // `PlayerSpawn` without a name.
+    let Some((spawn_transform, _)) = named_spawn
+        .or_else(|| {
+            spawns
+                .iter()
+                .find(|(_, spawn_object)| spawn_object.name.is_empty())
+        })
+        .or(spawns.first())
+    else {
+        return;
+    };
+
+    for (mut player_transform, mut respawn_point) in player_query.iter_mut() {
+        player_transform.translation.x = spawn_transform.translation.x;
+        player_transform.translation.y = spawn_transform.translation.y;
+        respawn_point.0 = spawn_transform.translation.truncate();
+    }
+}
+
+// The player isn't despawned when they die, they get their health back at their respawn point.
+fn respawn_player(
+    mut commands: Commands,
+    mut death_events: EventReader<DeathEvent>,
+    mut player_query: Query<(&mut Health, &MaxHealth, &mut Transform, &RespawnPoint), With<Player>>,
+    mut respawn_events: EventWriter<PlayerRespawned>,
+) {
+    for death in death_events.iter() {
+        let Ok((mut health, max_health, mut transform, respawn_point)) =
+            player_query.get_mut(death.entity)
+        else {
+            continue;
+        };
+
+        health.0 = max_health.0;
+        transform.translation.x = respawn_point.0.x;
+        transform.translation.y = respawn_point.0.y;
+        commands
+            .entity(death.entity)
+            .insert(StatusEffects::default())
+            .remove::<Invulnerable>();
+
+        info!("The player died and respawned");
+        respawn_events.send(PlayerRespawned {
+            entity: death.entity,
+        });
+    }
 }
 
 fn player_physics(_commands: Commands, mut query: Query<(&Player, &ColliderInfo, &mut Transform)>) {
@@ -149,11 +268,18 @@ fn player_physics(_commands: Commands, mut query: Query<(&Player, &ColliderInfo,
 }
 
 fn player_movement(
-    mut player_query: Query<(&mut Player, &Transform, &mut KinematicCharacterController)>,
+    mut player_query: Query<(
+        &mut Player,
+        &Transform,
+        &mut KinematicCharacterController,
+        &WalkSpeed,
+    )>,
     keyboard: Res<Input<KeyCode>>,
     time: Res<Time>,
 ) {
-    for (mut player, _, mut character) in player_query.iter_mut() {
+    for (mut player, _, mut character, walk_speed) in player_query.iter_mut() {
+        let speed = walk_speed.0;
+
         if keyboard.just_released(KeyCode::W)
             || keyboard.just_released(KeyCode::S)
             || keyboard.just_released(KeyCode::A)
@@ -172,25 +298,27 @@ fn player_movement(
 
         if keyboard.pressed(KeyCode::W) {
             player.facing_direction = FacingDirection::Up;
-            character.translation = Some(Vec2::new(0.0, player.speed * time.delta_seconds()));
+            character.translation = Some(Vec2::new(0.0, speed * time.delta_seconds()));
         }
         if keyboard.pressed(KeyCode::S) {
             player.facing_direction = FacingDirection::Down;
-            character.translation = Some(Vec2::new(0.0, -(player.speed * time.delta_seconds())));
+            character.translation = Some(Vec2::new(0.0, -(speed * time.delta_seconds())));
         }
         if keyboard.pressed(KeyCode::A) {
             player.facing_direction = FacingDirection::Left;
-            character.translation = Some(Vec2::new(-(player.speed * time.delta_seconds()), 0.0));
+            character.translation = Some(Vec2::new(-(speed * time.delta_seconds()), 0.0));
         }
         if keyboard.pressed(KeyCode::D) {
             player.facing_direction = FacingDirection::Right;
-            character.translation = Some(Vec2::new(player.speed * time.delta_seconds(), 0.0));
+            character.translation = Some(Vec2::new(speed * time.delta_seconds(), 0.0));
         }
     }
 }
 
 fn handle_idle(mut player_query: Query<(&mut Player, &mut TextureAtlasSprite)>) {
-    let (player, mut texture_atlas) = player_query.single_mut();
+    let Ok((player, mut texture_atlas)) = player_query.get_single_mut() else {
+        return;
+    };
 
     if player.idle {
         texture_atlas.index = 88;
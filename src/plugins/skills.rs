@@ -4,12 +4,16 @@ use bevy_rapier2d::prelude::{
     RigidBody, Velocity,
 };
 
-use crate::tiled::Wall;
+use crate::tiled::{Wall, YSort};
 
 use super::{
-    character_stats::{Damage, Health},
-    enemy::{AggroStatus, Enemy},
-    player::{FacingDirection, Player},
+    character_stats::{AttackDamage, Damage, DamageKind, Health, Resistances, SpellSpeed},
+    enemy::Enemy,
+    enemy_ai::EnemyAlerted,
+    health::DamageEvent,
+    map_transition::MapScoped,
+    player::Player,
+    status_effects::{ApplyStatus, StatusEffect, StatusEffects, StatusKind},
     utils::AnimationTimer,
 };
 
@@ -26,11 +30,15 @@ impl Plugin for SkillsPlugin {
     }
 }
 
+// Fireballs set what they hit on fire.
+const BURN_DAMAGE_PER_SECOND: f32 = 2.0;
+const BURN_SECONDS: f32 = 3.0;
+
 #[derive(Debug, Resource)]
 pub struct FireSpriteSheet(Handle<TextureAtlas>);
 
 #[derive(Debug, Component)]
-pub struct SummonedBy(u32);
+pub struct SummonedBy(pub Entity);
 
 fn load_fireball(
     mut commands: Commands,
@@ -62,15 +70,15 @@ fn handle_cooldown(mut player_query: Query<(&mut Player, &mut Cooldown)>, time:
     }
 }
 
+// Spawns a fireball flying with the given velocity, dealing fire damage. Fireballs don't hurt their
+// summoner, and the ones summoned by enemies don't hurt other enemies.
 pub fn create_fireball(
     commands: &mut Commands,
     fire_sprite_sheet: &Res<FireSpriteSheet>,
-    x: f32,
-    y: f32,
-    rotation: Quat,
-    facing_direction: FacingDirection,
-    speed: f32,
-    player: &Entity,
+    position: Vec2,
+    velocity: Vec2,
+    damage: f32,
+    summoner: Entity,
 ) {
     let sprite = TextureAtlasSprite {
         index: 0,
@@ -78,19 +86,16 @@ pub fn create_fireball(
         ..Default::default()
     };
 
-    let velocity = match facing_direction {
-        FacingDirection::Up => Vec2::new(0.0, speed),
-        FacingDirection::Down => Vec2::new(0.0, speed),
-        FacingDirection::Left => Vec2::new(speed, 0.0),
-        FacingDirection::Right => Vec2::new(speed, 0.0),
-    };
+    // The sprite points up.
+    let rotation = Quat::from_rotation_z(-velocity.x.atan2(velocity.y));
+
     commands
         .spawn((
             SpriteSheetBundle {
                 sprite,
                 texture_atlas: fire_sprite_sheet.0.clone(),
                 transform: Transform {
-                    translation: Vec3::new(x, y, 0.1),
+                    translation: position.extend(0.0),
                     rotation,
                     ..Default::default()
                 },
@@ -113,7 +118,9 @@ pub fn create_fireball(
                 )));
         })
         .insert(FireBall)
-        .insert(Damage(10.0))
+        .insert(YSort::default())
+        .insert(MapScoped)
+        .insert(Damage::new(damage, DamageKind::Fire))
         .insert(Cooldown(Timer::from_seconds(2.0, TimerMode::Once)))
         .insert(AnimationTimer(Timer::from_seconds(
             0.1,
@@ -123,7 +130,7 @@ pub fn create_fireball(
             linvel: velocity,
             angvel: 0.0,
         })
-        .insert(SummonedBy(player.index()));
+        .insert(SummonedBy(summoner));
 }
 
 fn animate_fireball(
@@ -148,18 +155,20 @@ fn animate_fireball(
 
 fn spawn_fireball(
     mut commands: Commands,
-    mut player_query: Query<(Entity, &mut Player, &Transform)>,
+    mut player_query: Query<(
+        Entity,
+        &mut Player,
+        &Transform,
+        &SpellSpeed,
+        &AttackDamage,
+        &StatusEffects,
+    )>,
     keyboard: Res<Input<KeyCode>>,
     fire_sprite_sheet: Res<FireSpriteSheet>,
 ) {
-    for (player_entity, mut player, transform) in player_query.iter_mut() {
-        let rotation = match player.facing_direction {
-            super::player::FacingDirection::Up => Quat::from_rotation_z(0.0),
-            super::player::FacingDirection::Down => Quat::from_rotation_z(3.14),
-            super::player::FacingDirection::Left => Quat::from_rotation_z(1.57),
-            super::player::FacingDirection::Right => Quat::from_rotation_z(-1.57),
-        };
-
+    for (player_entity, mut player, transform, spell_speed, attack_damage, status_effects) in
+        player_query.iter_mut()
+    {
         let x = match player.facing_direction {
             super::player::FacingDirection::Up => transform.translation.x,
             super::player::FacingDirection::Down => transform.translation.x,
@@ -174,24 +183,25 @@ fn spawn_fireball(
             super::player::FacingDirection::Right => transform.translation.y,
         };
 
-        let speed = match player.facing_direction {
-            super::player::FacingDirection::Up => player.spell_speed,
-            super::player::FacingDirection::Down => -(player.spell_speed),
-            super::player::FacingDirection::Left => -(player.spell_speed),
-            super::player::FacingDirection::Right => player.spell_speed,
+        let velocity = match player.facing_direction {
+            super::player::FacingDirection::Up => Vec2::new(0.0, spell_speed.0),
+            super::player::FacingDirection::Down => Vec2::new(0.0, -spell_speed.0),
+            super::player::FacingDirection::Left => Vec2::new(-spell_speed.0, 0.0),
+            super::player::FacingDirection::Right => Vec2::new(spell_speed.0, 0.0),
         };
 
-        if keyboard.just_released(KeyCode::Space) && player.can_recast {
+        if keyboard.just_released(KeyCode::Space)
+            && player.can_recast
+            && !status_effects.is_stunned()
+        {
             player.can_recast = false;
             create_fireball(
                 &mut commands,
                 &fire_sprite_sheet,
-                x,
-                y,
-                rotation,
-                player.facing_direction.clone(),
-                speed,
-                &player_entity,
+                Vec2::new(x, y),
+                velocity,
+                attack_damage.0,
+                player_entity,
             );
         }
     }
@@ -201,9 +211,9 @@ fn destroy_on_solid_wall(
     mut commands: Commands,
     rapier_context: Res<RapierContext>,
     wall_query: Query<(Entity, With<Wall>, With<ActiveEvents>)>,
-    fireball_collider_query: Query<(Entity, &Parent, With<Collider>, With<ActiveEvents>)>,
+    fireball_collider_query: Query<(Entity, &Parent), With<FireBallCollider>>,
 ) {
-    for (fireball, parent, _, _) in fireball_collider_query.iter() {
+    for (fireball, parent) in fireball_collider_query.iter() {
         for (wall, _, _) in wall_query.iter() {
             if let Some(contact_pair) = rapier_context.contact_pair(wall, fireball) {
                 if contact_pair.has_any_active_contacts() {
@@ -219,49 +229,75 @@ fn destroy_on_solid_wall(
 fn destroy_on_characters(
     mut commands: Commands,
     rapier_context: Res<RapierContext>,
-    mut character_query: Query<(
+    character_query: Query<
         Entity,
-        &mut Health,
-        With<KinematicCharacterController>,
-        With<ActiveEvents>,
-    )>,
-    collider_query: Query<(Entity, &Parent, With<Collider>, With<ActiveEvents>)>,
-    damage_query: Query<(Entity, &Damage, &SummonedBy)>,
-    attacker_query: Query<(Entity, &Player)>,
-    mut enemy_query: Query<(Entity, &mut AggroStatus)>,
+        (
+            With<Health>,
+            With<KinematicCharacterController>,
+            With<ActiveEvents>,
+        ),
+    >,
+    collider_query: Query<(Entity, &Parent), With<FireBallCollider>>,
+    damage_query: Query<(&Damage, &SummonedBy)>,
+    attacker_query: Query<Entity, With<Player>>,
+    enemy_query: Query<Entity, With<Enemy>>,
+    resistance_query: Query<&Resistances>,
+    mut alert_events: EventWriter<EnemyAlerted>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut status_events: EventWriter<ApplyStatus>,
 ) {
-    // let attacker = None;
-    for (fireball, parent, _, _) in collider_query.iter() {
-        for (character, mut health, _, _) in character_query.iter_mut() {
+    for (fireball, parent) in collider_query.iter() {
+        let Ok((damage, summoned_by)) = damage_query.get(parent.get()) else {
+            continue;
+        };
+
+        // Fireballs don't hurt their summoner, and enemies don't hurt each other.
+        let is_friendly = |character: Entity| {
+            character == summoned_by.0
+                || (enemy_query.contains(character) && enemy_query.contains(summoned_by.0))
+        };
+
+        for character in character_query.iter() {
+            if is_friendly(character) {
+                continue;
+            }
+
             if let Some(contact_pair) = rapier_context.contact_pair(character, fireball) {
                 if contact_pair.has_any_active_contacts()
                     && commands.get_entity(parent.get()).is_some()
                 {
-                    for (found_entity, damage, summoned_by) in damage_query.iter() {
-                        if found_entity.index() == parent.get().index() {
-                            for (attacker_entity, _) in attacker_query.iter() {
-                                for (enemy_entity, mut aggro_status) in enemy_query.iter_mut() {
-                                    if summoned_by.0 == attacker_entity.index()
-                                        && character.index() == enemy_entity.index()
-                                    {
-                                        if *aggro_status == AggroStatus::Neutral {
-                                            *aggro_status =
-                                                AggroStatus::Alerted(attacker_entity.index());
-                                        }
-                                    }
-                                }
-                            }
+                    if enemy_query.contains(character) && attacker_query.contains(summoned_by.0) {
+                        alert_events.send(EnemyAlerted {
+                            enemy: character,
+                            target: summoned_by.0,
+                        });
+                    }
 
-                            health.0 -= damage.0;
+                    damage_events.send(DamageEvent::new(
+                        Some(summoned_by.0),
+                        character,
+                        damage.amount,
+                        damage.kind,
+                    ));
 
-                            if health.0 <= 0.0 {
-                                if commands.get_entity(character).is_some() {
-                                    commands.entity(character).despawn_recursive();
-                                }
-                            }
-                        }
+                    // Characters immune to the fireball don't catch fire either.
+                    let is_immune = resistance_query
+                        .get(character)
+                        .is_ok_and(|resistances| resistances.get(damage.kind) >= 1.0);
+                    if !is_immune {
+                        status_events.send(ApplyStatus {
+                            target: character,
+                            effect: StatusEffect::new(
+                                StatusKind::Burn,
+                                BURN_DAMAGE_PER_SECOND,
+                                BURN_SECONDS,
+                            )
+                            .with_source(summoned_by.0),
+                        });
                     }
+
                     commands.entity(parent.get()).despawn_recursive();
+                    break;
                 }
             }
         }
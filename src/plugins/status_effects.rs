@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+
+use super::{
+    character_stats::{set_percent_modifier, DamageKind, Stat, Stats},
+    health::{DamageEvent, HealEvent},
+};
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatus>()
+            .add_system(apply_statuses)
+            .add_system(tick_statuses.after(apply_statuses))
+            .add_system(tint_afflicted.after(tick_statuses));
+    }
+}
+
+// How often burn, poison and regen deal their damage or healing.
+const TICK_SECONDS: f32 = 0.5;
+// How many poison applications add up.
+const MAX_POISON_STACKS: usize = 5;
+// The sources of the walk speed modifiers of slow and stun.
+const SLOW_MODIFIER_SOURCE: &str = "slow";
+const STUN_MODIFIER_SOURCE: &str = "stun";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusKind {
+    // Fire damage over time.
+    Burn,
+    // Lowers the walk speed by a fraction.
+    Slow,
+    // Can't move, attack or cast.
+    Stun,
+    // Poison damage over time, which stacks.
+    Poison,
+    // Healing over time.
+    Regen,
+}
+
+impl StatusKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "burn" => Some(Self::Burn),
+            "slow" => Some(Self::Slow),
+            "stun" => Some(Self::Stun),
+            "poison" => Some(Self::Poison),
+            "regen" => Some(Self::Regen),
+            _ => None,
+        }
+    }
+
+    // Whether applying the effect again adds a stack instead of refreshing the existing one.
+    fn stacks(&self) -> bool {
+        *self == Self::Poison
+    }
+
+    // The tint of afflicted sprites. Earlier kinds take priority when several are active.
+    fn color(&self) -> Color {
+        match self {
+            Self::Stun => Color::rgb(1.0, 1.0, 0.4),
+            Self::Burn => Color::rgb(1.0, 0.55, 0.3),
+            Self::Poison => Color::rgb(0.55, 1.0, 0.45),
+            Self::Slow => Color::rgb(0.55, 0.75, 1.0),
+            Self::Regen => Color::rgb(1.0, 0.75, 0.85),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    // Damage or healing per second for burn, poison and regen, the fraction of speed lost for
+    // slow. Unused by stun.
+    pub magnitude: f32,
+    pub seconds: f32,
+    // The character that applied the effect, which is credited for its damage.
+    pub source: Option<Entity>,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, magnitude: f32, seconds: f32) -> Self {
+        Self {
+            kind,
+            magnitude,
+            seconds,
+            source: None,
+        }
+    }
+
+    pub fn with_source(mut self, source: Entity) -> Self {
+        self.source = Some(source);
+        self
+    }
+}
+
+// Applies an effect to a character with `StatusEffects`. Reapplying an effect that doesn't stack
+// keeps the strongest magnitude and the longest remaining duration.
+#[derive(Debug, Clone, Copy)]
+pub struct ApplyStatus {
+    pub target: Entity,
+    pub effect: StatusEffect,
+}
+
+#[derive(Debug)]
+struct ActiveStatus {
+    effect: StatusEffect,
+    duration: Timer,
+    tick: Timer,
+}
+
+impl ActiveStatus {
+    fn new(effect: StatusEffect) -> Self {
+        Self {
+            effect,
+            duration: Timer::from_seconds(effect.seconds, TimerMode::Once),
+            tick: Timer::from_seconds(TICK_SECONDS, TimerMode::Repeating),
+        }
+    }
+}
+
+// The effects active on a character. Slow and stun lower its `WalkSpeed` through modifiers on its
+// `Stats` while they last.
+#[derive(Debug, Default, Component)]
+pub struct StatusEffects {
+    active: Vec<ActiveStatus>,
+}
+
+impl StatusEffects {
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.active.iter().any(|status| status.effect.kind == kind)
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.has(StatusKind::Stun)
+    }
+
+    // The fraction of walk speed lost to the strongest slow.
+    fn slow(&self) -> f32 {
+        self.active
+            .iter()
+            .filter(|status| status.effect.kind == StatusKind::Slow)
+            .map(|status| status.effect.magnitude)
+            .fold(0.0, f32::max)
+            .min(1.0)
+    }
+
+    fn apply(&mut self, effect: StatusEffect) {
+        if effect.kind.stacks() {
+            let stacks = self
+                .active
+                .iter()
+                .filter(|status| status.effect.kind == effect.kind)
+                .count();
+            if stacks < MAX_POISON_STACKS {
+                self.active.push(ActiveStatus::new(effect));
+                return;
+            }
+        }
+
+        // Refresh the existing effect, or the stack closest to running out.
+        let existing = self
+            .active
+            .iter_mut()
+            .filter(|status| status.effect.kind == effect.kind)
+            .min_by(|a, b| a.duration.remaining().cmp(&b.duration.remaining()));
+
+        let Some(existing) = existing else {
+            self.active.push(ActiveStatus::new(effect));
+            return;
+        };
+
+        let remaining = existing.duration.remaining_secs().max(effect.seconds);
+        existing.effect.magnitude = existing.effect.magnitude.max(effect.magnitude);
+        existing.effect.source = effect.source.or(existing.effect.source);
+        existing.duration = Timer::from_seconds(remaining, TimerMode::Once);
+    }
+}
+
+fn apply_statuses(
+    mut status_events: EventReader<ApplyStatus>,
+    mut status_query: Query<&mut StatusEffects>,
+) {
+    for event in status_events.iter() {
+        if let Ok(mut status_effects) = status_query.get_mut(event.target) {
+            status_effects.apply(event.effect);
+        }
+    }
+}
+
+fn tick_statuses(
+    mut status_query: Query<(Entity, &mut StatusEffects, Option<&mut Stats>)>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut heal_events: EventWriter<HealEvent>,
+    time: Res<Time>,
+) {
+    for (entity, mut status_effects, stats) in status_query.iter_mut() {
+        // Percent modifiers add up, so a stun takes away the whole walk speed.
+        if let Some(mut stats) = stats {
+            let (slow, stun) = (status_effects.slow(), status_effects.is_stunned());
+            set_percent_modifier(&mut stats, Stat::WalkSpeed, -slow, SLOW_MODIFIER_SOURCE);
+            set_percent_modifier(
+                &mut stats,
+                Stat::WalkSpeed,
+                if stun { -1.0 } else { 0.0 },
+                STUN_MODIFIER_SOURCE,
+            );
+        }
+
+        if status_effects.active.is_empty() {
+            continue;
+        }
+
+        for status in status_effects.active.iter_mut() {
+            status.duration.tick(time.delta());
+            let ticks = status.tick.tick(time.delta()).times_finished_this_tick();
+            if ticks == 0 {
+                continue;
+            }
+
+            let amount = status.effect.magnitude * TICK_SECONDS * ticks as f32;
+            let damage_kind = match status.effect.kind {
+                StatusKind::Burn => DamageKind::Fire,
+                StatusKind::Poison => DamageKind::Poison,
+                StatusKind::Regen => {
+                    heal_events.send(HealEvent {
+                        target: entity,
+                        amount,
+                    });
+                    continue;
+                }
+                StatusKind::Slow | StatusKind::Stun => continue,
+            };
+
+            damage_events.send(
+                DamageEvent::new(status.effect.source, entity, amount, damage_kind).over_time(),
+            );
+        }
+
+        status_effects
+            .active
+            .retain(|status| !status.duration.finished());
+    }
+}
+
+fn tint_afflicted(
+    mut sprite_query: Query<(&StatusEffects, &mut TextureAtlasSprite), Changed<StatusEffects>>,
+) {
+    for (status_effects, mut sprite) in sprite_query.iter_mut() {
+        let color = [
+            StatusKind::Stun,
+            StatusKind::Burn,
+            StatusKind::Poison,
+            StatusKind::Slow,
+            StatusKind::Regen,
+        ]
+        .into_iter()
+        .find(|kind| status_effects.has(*kind))
+        .map_or(Color::WHITE, |kind| kind.color());
+
+        if sprite.color != color {
+            sprite.color = color;
+        }
+    }
+}
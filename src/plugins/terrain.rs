@@ -0,0 +1,150 @@
+use bevy::prelude::*;
+use bevy_ecs_tilemap::prelude::{TilePos, TileStorage, TilemapGridSize, TilemapSize, TilemapType};
+
+use crate::tiled::TileProperties;
+
+use super::{
+    character_stats::{set_percent_modifier, DamageKind, Stat, Stats},
+    health::{DamageEvent, HealEvent},
+    status_effects::{ApplyStatus, StatusEffect, StatusKind},
+};
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(apply_terrain_effects);
+    }
+}
+
+// The source of the walk speed modifier of slowing tiles.
+const TERRAIN_MODIFIER_SOURCE: &str = "terrain";
+
+// How long the status effects of tiles last once applied. They're applied again every
+// `TERRAIN_STATUS_INTERVAL` seconds while standing on the tile, so they wear off soon after leaving.
+const TERRAIN_STATUS_SECONDS: f32 = 2.0;
+const TERRAIN_STATUS_INTERVAL: f32 = 1.0;
+
+// Tracks when the status effects of the tiles a character is standing on are applied. Slowing
+// tiles lower the character's `WalkSpeed` through a modifier on its `Stats`.
+#[derive(Debug, Component)]
+pub struct Terrain {
+    status_timer: Timer,
+}
+
+impl Default for Terrain {
+    fn default() -> Self {
+        let mut status_timer = Timer::from_seconds(TERRAIN_STATUS_INTERVAL, TimerMode::Once);
+        status_timer.set_elapsed(status_timer.duration());
+
+        Self { status_timer }
+    }
+}
+
+fn apply_terrain_effects(
+    tilemap_query: Query<(
+        &TileStorage,
+        &TilemapSize,
+        &TilemapGridSize,
+        &TilemapType,
+        &GlobalTransform,
+    )>,
+    tile_query: Query<&TileProperties>,
+    mut character_query: Query<(Entity, &GlobalTransform, &mut Terrain, Option<&mut Stats>)>,
+    time: Res<Time>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut heal_events: EventWriter<HealEvent>,
+    mut status_events: EventWriter<ApplyStatus>,
+) {
+    for (character, character_transform, mut terrain, stats) in character_query.iter_mut() {
+        let mut speed_multiplier = 1.0;
+        let mut damages = Vec::new();
+        let mut heal_per_second = 0.0;
+        let mut status_effects = Vec::new();
+
+        // Every layer under the character contributes, e.g. a puddle drawn on top of the floor.
+        for (tile_storage, map_size, grid_size, map_type, map_transform) in tilemap_query.iter() {
+            let local_position = map_transform
+                .compute_matrix()
+                .inverse()
+                .transform_point3(character_transform.translation());
+
+            let Some(tile_pos) =
+                TilePos::from_world_pos(&local_position.truncate(), map_size, grid_size, map_type)
+            else {
+                continue;
+            };
+
+            let Some(properties) = tile_storage
+                .get(&tile_pos)
+                .and_then(|tile| tile_query.get(tile).ok())
+            else {
+                continue;
+            };
+
+            speed_multiplier *= 1.0 - properties.slow;
+            heal_per_second += properties.heal_per_second;
+
+            if properties.damage_per_second > 0.0 {
+                let name = properties.damage_kind.as_deref().unwrap_or("fire");
+                match DamageKind::from_name(name) {
+                    Some(kind) => damages.push((properties.damage_per_second, kind)),
+                    None => warn!("Unknown damage kind `{name}` on a tile."),
+                }
+            }
+
+            if let Some(name) = &properties.status_effect {
+                match StatusKind::from_name(name) {
+                    Some(kind) => status_effects.push(StatusEffect::new(
+                        kind,
+                        properties.status_magnitude,
+                        TERRAIN_STATUS_SECONDS,
+                    )),
+                    None => warn!("Unknown status effect `{name}` on a tile."),
+                }
+            }
+        }
+
+        if let Some(mut stats) = stats {
+            let percent = speed_multiplier.max(0.0) - 1.0;
+            set_percent_modifier(
+                &mut stats,
+                Stat::WalkSpeed,
+                percent,
+                TERRAIN_MODIFIER_SOURCE,
+            );
+        }
+
+        // The timer stays finished while off those tiles, so stepping on one applies its effects
+        // right away.
+        terrain.status_timer.tick(time.delta());
+        if !status_effects.is_empty() && terrain.status_timer.finished() {
+            terrain.status_timer.reset();
+            for effect in status_effects {
+                status_events.send(ApplyStatus {
+                    target: character,
+                    effect,
+                });
+            }
+        }
+
+        if heal_per_second > 0.0 {
+            heal_events.send(HealEvent {
+                target: character,
+                amount: heal_per_second * time.delta_seconds(),
+            });
+        }
+
+        for (damage_per_second, kind) in damages {
+            damage_events.send(
+                DamageEvent::new(
+                    None,
+                    character,
+                    damage_per_second * time.delta_seconds(),
+                    kind,
+                )
+                .over_time(),
+            );
+        }
+    }
+}
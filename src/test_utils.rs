@@ -0,0 +1,49 @@
+// Helpers shared by the tests of several modules.
+
+use bevy::utils::HashMap;
+
+use crate::tiled::{flatten_layers, MapGrid, TileProperties, TiledMap};
+
+// Builds a grid from rows drawn top to bottom, with `#` marking solid cells. Returns the cells row
+// by row from the bottom, along with the width and height of the grid.
+pub fn solid_grid(rows: &[&str]) -> (Vec<bool>, u32, u32) {
+    let solid = rows
+        .iter()
+        .rev()
+        .flat_map(|row| row.chars().map(|cell| cell == '#'))
+        .collect();
+
+    (solid, rows[0].len() as u32, rows.len() as u32)
+}
+
+// Loads a TMX file along with the properties the asset loader parses, but without textures.
+pub fn load_map(path: &str) -> TiledMap {
+    let map = tiled::Loader::new().load_tmx_map(path).unwrap();
+    let grid = MapGrid::new(&map, &std::fs::read(path).unwrap()).unwrap();
+
+    let mut tile_properties = HashMap::default();
+    for (tileset_index, tileset) in map.tilesets().iter().enumerate() {
+        for (tile_id, tile) in tileset.tiles() {
+            let properties = TileProperties::parse(&tile.properties).unwrap();
+            tile_properties.insert((tileset_index, tile_id), properties);
+        }
+    }
+    let layer_properties = flatten_layers(&map)
+        .iter()
+        .map(|flat_layer| {
+            let properties = TileProperties::parse(&flat_layer.layer.properties).unwrap();
+            (flat_layer.layer.id(), properties)
+        })
+        .collect();
+
+    TiledMap {
+        map,
+        grid,
+        tilemap_textures: HashMap::default(),
+        #[cfg(not(feature = "atlas"))]
+        tile_image_offsets: HashMap::default(),
+        tile_properties,
+        layer_properties,
+        image_layer_textures: HashMap::default(),
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use bevy::prelude::{Component, Query, Res, Time};
+use bevy_ecs_tilemap::prelude::TileTextureIndex;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnimationFrame {
+    pub texture_index: u32,
+    pub duration: Duration,
+}
+
+// Plays the animation authored for a tile in Tiled, using the duration of each frame.
+#[derive(Debug, Component)]
+pub struct TiledAnimation {
+    frames: Vec<AnimationFrame>,
+    current_frame: usize,
+    // The time spent on the current frame.
+    elapsed: Duration,
+}
+
+impl TiledAnimation {
+    pub fn new(frames: Vec<AnimationFrame>) -> Self {
+        Self {
+            frames,
+            current_frame: 0,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    // The texture of the current frame.
+    pub fn texture_index(&self) -> Option<u32> {
+        self.frames
+            .get(self.current_frame)
+            .map(|frame| frame.texture_index)
+    }
+
+    // Moves the animation forward, carrying the time left over from each frame into the next one
+    // so the animation keeps its authored speed. Returns whether the frame changed.
+    fn advance(&mut self, delta: Duration) -> bool {
+        // Frames without a duration would never be left.
+        if self.frames.iter().all(|frame| frame.duration.is_zero()) {
+            return false;
+        }
+
+        self.elapsed += delta;
+        let mut changed = false;
+        while self.elapsed >= self.frames[self.current_frame].duration {
+            self.elapsed -= self.frames[self.current_frame].duration;
+            self.current_frame = (self.current_frame + 1) % self.frames.len();
+            changed = true;
+        }
+        changed
+    }
+}
+
+pub fn animate_tiles(
+    time: Res<Time>,
+    mut tile_query: Query<(&mut TiledAnimation, &mut TileTextureIndex)>,
+) {
+    for (mut animation, mut texture_index) in tile_query.iter_mut() {
+        if !animation.advance(time.delta()) {
+            continue;
+        }
+
+        if let Some(index) = animation.texture_index() {
+            texture_index.0 = index;
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::prelude::Vec2;
+use bevy_ecs_tilemap::prelude::{
+    HexCoordSystem, TileFlip, TilemapGridSize, TilemapTileSize, TilemapType,
+};
+use bevy_rapier2d::{
+    math::{Rot, Vect},
+    prelude::Collider,
+};
+
+const ELLIPSE_SEGMENTS: usize = 16;
+
+// The colliders built from the shapes drawn in Tiled's tile collision editor, centered on the tile.
+// Compound colliders can't contain composite shapes, so `shapes` only holds convex parts that can
+// be merged into the compound of their layer, while each polyline needs its own collider.
+#[derive(Default)]
+pub struct TileColliders {
+    pub shapes: Vec<(Vect, Rot, Collider)>,
+    pub polylines: Vec<Collider>,
+}
+
+pub fn tile_collider(
+    tile: &tiled::TileData,
+    tile_size: &TilemapTileSize,
+    flip: &TileFlip,
+) -> Option<TileColliders> {
+    let collision = tile.collision.as_ref()?;
+
+    let mut colliders = TileColliders::default();
+    for object in collision.object_data() {
+        add_object_colliders(&mut colliders, object, tile_size, flip);
+    }
+
+    if colliders.shapes.is_empty() && colliders.polylines.is_empty() {
+        return None;
+    }
+
+    Some(colliders)
+}
+
+fn add_object_colliders(
+    colliders: &mut TileColliders,
+    object: &tiled::ObjectData,
+    tile_size: &TilemapTileSize,
+    flip: &TileFlip,
+) {
+    let to_tile = |point: Vec2| flip_point(tile_point(object, point, tile_size), flip);
+
+    match &object.shape {
+        tiled::ObjectShape::Rect { width, height } if object.rotation == 0.0 => {
+            let center = to_tile(Vec2::new(width / 2.0, height / 2.0));
+            let (half_x, half_y) = match flip.d {
+                true => (height / 2.0, width / 2.0),
+                false => (width / 2.0, height / 2.0),
+            };
+
+            colliders
+                .shapes
+                .push((center, 0.0, Collider::cuboid(half_x, half_y)));
+        }
+        tiled::ObjectShape::Rect { width, height } => {
+            let corners = [
+                Vec2::ZERO,
+                Vec2::new(*width, 0.0),
+                Vec2::new(*width, *height),
+                Vec2::new(0.0, *height),
+            ];
+            let vertices = corners.into_iter().map(to_tile).collect();
+
+            colliders.shapes.extend(
+                Collider::convex_polyline(vertices).map(|collider| (Vec2::ZERO, 0.0, collider)),
+            );
+        }
+        tiled::ObjectShape::Ellipse { width, height } if width == height => {
+            let center = to_tile(Vec2::new(width / 2.0, height / 2.0));
+
+            colliders
+                .shapes
+                .push((center, 0.0, Collider::ball(width / 2.0)));
+        }
+        tiled::ObjectShape::Ellipse { width, height } => {
+            // Rapier has no ellipse shape, so approximate it with a convex polygon.
+            let radius = Vec2::new(width / 2.0, height / 2.0);
+            let vertices = (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+                    to_tile(radius + radius * Vec2::new(angle.cos(), angle.sin()))
+                })
+                .collect();
+
+            colliders.shapes.extend(
+                Collider::convex_polyline(vertices).map(|collider| (Vec2::ZERO, 0.0, collider)),
+            );
+        }
+        tiled::ObjectShape::Polygon { points } => {
+            let vertices = points
+                .iter()
+                .map(|&(x, y)| to_tile(Vec2::new(x, y)))
+                .collect::<Vec<_>>();
+            // Polygons may be concave, so decompose the outline into convex parts. The
+            // decomposition is itself a compound, so its parts are added one by one.
+            let indices = (0..vertices.len() as u32)
+                .map(|i| [i, (i + 1) % vertices.len() as u32])
+                .collect::<Vec<_>>();
+            let decomposition = Collider::convex_decomposition(&vertices, &indices);
+
+            if let Some(compound) = decomposition.raw.as_compound() {
+                colliders
+                    .shapes
+                    .extend(compound.shapes().iter().map(|(isometry, shape)| {
+                        (
+                            Vect::new(isometry.translation.x, isometry.translation.y),
+                            isometry.rotation.angle(),
+                            Collider::from(shape.clone()),
+                        )
+                    }));
+            }
+        }
+        tiled::ObjectShape::Polyline { points } => {
+            let vertices = points
+                .iter()
+                .map(|&(x, y)| to_tile(Vec2::new(x, y)))
+                .collect();
+
+            colliders.polylines.push(Collider::polyline(vertices, None));
+        }
+        _ => (),
+    }
+}
+
+// Converts a point relative to the object's origin into a point relative to the tile center.
+// Tiled measures from the top left of the tile with y pointing down and rotates objects clockwise
+// around their origin.
+fn tile_point(object: &tiled::ObjectData, point: Vec2, tile_size: &TilemapTileSize) -> Vec2 {
+    let (sin, cos) = object.rotation.to_radians().sin_cos();
+    let rotated = Vec2::new(point.x * cos - point.y * sin, point.x * sin + point.y * cos);
+
+    Vec2::new(
+        object.x + rotated.x - tile_size.x / 2.0,
+        tile_size.y / 2.0 - (object.y + rotated.y),
+    )
+}
+
+// Tiled applies the diagonal flip first, which swaps the x and y axes, then the horizontal and
+// vertical flips.
+fn flip_point(point: Vec2, flip: &TileFlip) -> Vec2 {
+    let mut point = point;
+    if flip.d {
+        point = Vec2::new(-point.y, -point.x);
+    }
+    if flip.x {
+        point.x = -point.x;
+    }
+    if flip.y {
+        point.y = -point.y;
+    }
+    point
+}
+
+// The outline of a whole tile for grids whose tiles aren't rectangles, centered on the tile.
+pub fn tile_footprint(map_type: &TilemapType, grid_size: &TilemapGridSize) -> Option<Collider> {
+    let (half_x, half_y) = (grid_size.x / 2.0, grid_size.y / 2.0);
+
+    let vertices = match map_type {
+        TilemapType::Square => return Some(Collider::cuboid(half_x, half_y)),
+        TilemapType::Isometric(_) => vec![
+            Vec2::new(0.0, half_y),
+            Vec2::new(half_x, 0.0),
+            Vec2::new(0.0, -half_y),
+            Vec2::new(-half_x, 0.0),
+        ],
+        // Row based hexagons point up, column based ones are flat on top.
+        TilemapType::Hexagon(
+            HexCoordSystem::Row | HexCoordSystem::RowEven | HexCoordSystem::RowOdd,
+        ) => {
+            vec![
+                Vec2::new(0.0, half_y),
+                Vec2::new(half_x, half_y / 2.0),
+                Vec2::new(half_x, -half_y / 2.0),
+                Vec2::new(0.0, -half_y),
+                Vec2::new(-half_x, -half_y / 2.0),
+                Vec2::new(-half_x, half_y / 2.0),
+            ]
+        }
+        TilemapType::Hexagon(_) => vec![
+            Vec2::new(half_x, 0.0),
+            Vec2::new(half_x / 2.0, half_y),
+            Vec2::new(-half_x / 2.0, half_y),
+            Vec2::new(-half_x, 0.0),
+            Vec2::new(-half_x / 2.0, -half_y),
+            Vec2::new(half_x / 2.0, -half_y),
+        ],
+    };
+
+    Collider::convex_polyline(vertices)
+}
+
+// A rectangle of solid tiles, measured in tiles from the bottom left of the layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TileRect {
+    // Returns the rectangle as a part of a compound collider placed on the tilemap.
+    pub fn collider_shape(
+        &self,
+        grid_size: &TilemapGridSize,
+        tile_size: &TilemapTileSize,
+    ) -> (Vect, Rot, Collider) {
+        let center = Vec2::new(
+            (self.x as f32 + (self.width - 1) as f32 / 2.0) * grid_size.x,
+            (self.y as f32 + (self.height - 1) as f32 / 2.0) * grid_size.y,
+        );
+        let half_x = ((self.width - 1) as f32 * grid_size.x + tile_size.x) / 2.0;
+        let half_y = ((self.height - 1) as f32 * grid_size.y + tile_size.y) / 2.0;
+
+        (center, 0.0, Collider::cuboid(half_x, half_y))
+    }
+}
+
+// Greedily merges the solid cells of a row-major grid into rectangles. Each rectangle is grown as
+// far right as possible and then as far up as its whole width allows.
+pub fn merge_solid_tiles(solid: &[bool], width: u32, height: u32) -> Vec<TileRect> {
+    let index = |x: u32, y: u32| (y * width + x) as usize;
+    let mut covered = vec![false; solid.len()];
+    let mut rects = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            if !solid[index(x, y)] || covered[index(x, y)] {
+                continue;
+            }
+
+            let is_free = |x: u32, y: u32| solid[index(x, y)] && !covered[index(x, y)];
+
+            let mut rect_width = 1;
+            while x + rect_width < width && is_free(x + rect_width, y) {
+                rect_width += 1;
+            }
+
+            let mut rect_height = 1;
+            while y + rect_height < height
+                && (x..x + rect_width).all(|column| is_free(column, y + rect_height))
+            {
+                rect_height += 1;
+            }
+
+            for covered_y in y..y + rect_height {
+                for covered_x in x..x + rect_width {
+                    covered[index(covered_x, covered_y)] = true;
+                }
+            }
+
+            rects.push(TileRect {
+                x,
+                y,
+                width: rect_width,
+                height: rect_height,
+            });
+        }
+    }
+
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::solid_grid;
+
+    // Every solid cell is covered by exactly one rectangle and no empty cell is covered.
+    fn assert_covers(solid: &[bool], width: u32, rects: &[TileRect]) {
+        let mut covered = vec![0; solid.len()];
+        for rect in rects {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    covered[(y * width + x) as usize] += 1;
+                }
+            }
+        }
+
+        for (index, (&solid, &count)) in solid.iter().zip(covered.iter()).enumerate() {
+            assert_eq!(count, solid as i32, "cell {index} is covered {count} times");
+        }
+    }
+
+    #[test]
+    fn merges_single_tile() {
+        let (solid, width, height) = solid_grid(&["...", ".#.", "..."]);
+        let rects = merge_solid_tiles(&solid, width, height);
+
+        assert_eq!(
+            rects,
+            vec![TileRect {
+                x: 1,
+                y: 1,
+                width: 1,
+                height: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn merges_full_grid_into_one_rect() {
+        let (solid, width, height) = solid_grid(&["####", "####", "####"]);
+        let rects = merge_solid_tiles(&solid, width, height);
+
+        assert_eq!(
+            rects,
+            vec![TileRect {
+                x: 0,
+                y: 0,
+                width: 4,
+                height: 3
+            }]
+        );
+    }
+
+    #[test]
+    fn merges_empty_grid_into_nothing() {
+        let (solid, width, height) = solid_grid(&["...", "..."]);
+
+        assert!(merge_solid_tiles(&solid, width, height).is_empty());
+    }
+
+    #[test]
+    fn merges_l_shape_into_two_rects() {
+        let (solid, width, height) = solid_grid(&["#..", "#..", "###"]);
+        let rects = merge_solid_tiles(&solid, width, height);
+
+        assert_eq!(rects.len(), 2);
+        assert_covers(&solid, width, &rects);
+    }
+
+    #[test]
+    fn merges_around_hole() {
+        let (solid, width, height) = solid_grid(&["###", "#.#", "###"]);
+        let rects = merge_solid_tiles(&solid, width, height);
+
+        assert_eq!(rects.len(), 4);
+        assert_covers(&solid, width, &rects);
+    }
+
+    #[test]
+    fn merges_room_walls() {
+        let (solid, width, height) =
+            solid_grid(&["######", "#....#", "#.##.#", "#....#", "######"]);
+        let rects = merge_solid_tiles(&solid, width, height);
+
+        assert_eq!(rects.len(), 5);
+        assert_covers(&solid, width, &rects);
+    }
+}
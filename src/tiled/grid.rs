@@ -0,0 +1,344 @@
+// Where Tiled puts the tiles of a map, and where they go in the tilemaps of bevy_ecs_tilemap.
+//
+// The world position of a point is its position in Tiled's pixels with y pointing up, relative to
+// the center of the map's bounding box. Tiled counts rows from the top while bevy_ecs_tilemap
+// counts them from the bottom, and bevy_ecs_tilemap's staggered grids are slanted rather than
+// zigzagging, so tiles are renumbered when they are put in a tilemap.
+
+use anyhow::{anyhow, Result};
+use bevy::prelude::{Transform, Vec2};
+use bevy_ecs_tilemap::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MapGrid {
+    pub orientation: tiled::Orientation,
+    pub width: u32,
+    pub height: u32,
+    pub tile_size: Vec2,
+    // Whether the odd rows of staggered and hexagonal maps are pushed right, instead of the even
+    // ones.
+    stagger_odd: bool,
+    // Where the camera has to be, in pixels, for layers with a parallax factor to be drawn at
+    // their position in the map.
+    parallax_origin: Vec2,
+}
+
+impl MapGrid {
+    // `tiled` doesn't expose the stagger settings or the parallax origin, so they are read from the
+    // TMX file itself.
+    // bevy_ecs_tilemap only staggers rows and its hexagons have sides half as long as they are
+    // tall, other maps would end up with gaps between their tiles.
+    pub fn new(map: &tiled::Map, tmx: &[u8]) -> Result<Self> {
+        let tmx = std::str::from_utf8(tmx)?;
+        let tile_size = Vec2::new(map.tile_width as f32, map.tile_height as f32);
+
+        if matches!(
+            map.orientation,
+            tiled::Orientation::Staggered | tiled::Orientation::Hexagonal
+        ) && map_attribute(tmx, "staggeraxis").unwrap_or("y") != "y"
+        {
+            return Err(anyhow!("Only maps with staggered rows are supported"));
+        }
+
+        if map.orientation == tiled::Orientation::Hexagonal {
+            let side_length: f32 = map_attribute(tmx, "hexsidelength").unwrap_or("0").parse()?;
+            if side_length != tile_size.y / 2.0 {
+                return Err(anyhow!(
+                    "Hexagonal maps need a hexsidelength of half the tile height ({}), found {side_length}",
+                    tile_size.y / 2.0
+                ));
+            }
+        }
+
+        Ok(Self {
+            orientation: map.orientation,
+            width: map.width,
+            height: map.height,
+            tile_size,
+            stagger_odd: map_attribute(tmx, "staggerindex").unwrap_or("odd") == "odd",
+            parallax_origin: Vec2::new(
+                map_attribute(tmx, "parallaxoriginx")
+                    .unwrap_or("0")
+                    .parse()?,
+                map_attribute(tmx, "parallaxoriginy")
+                    .unwrap_or("0")
+                    .parse()?,
+            ),
+        })
+    }
+
+    pub fn grid_size(&self) -> TilemapGridSize {
+        TilemapGridSize {
+            x: self.tile_size.x,
+            y: self.tile_size.y,
+        }
+    }
+
+    // The distance between two rows of tiles.
+    fn row_height(&self) -> f32 {
+        match self.orientation {
+            tiled::Orientation::Orthogonal => self.tile_size.y,
+            tiled::Orientation::Isometric | tiled::Orientation::Staggered => self.tile_size.y / 2.0,
+            tiled::Orientation::Hexagonal => self.tile_size.y * 0.75,
+        }
+    }
+
+    fn is_pushed_right(&self, y: i32) -> bool {
+        (y.rem_euclid(2) == 1) == self.stagger_odd
+    }
+
+    // The size of the map's bounding box in pixels.
+    pub fn size(&self) -> Vec2 {
+        let (width, height) = (self.width as f32, self.height as f32);
+
+        match self.orientation {
+            tiled::Orientation::Orthogonal => Vec2::new(width, height) * self.tile_size,
+            tiled::Orientation::Isometric => (width + height) / 2.0 * self.tile_size,
+            tiled::Orientation::Staggered | tiled::Orientation::Hexagonal => {
+                let pushed = match self.height > 1 {
+                    true => self.tile_size.x / 2.0,
+                    false => 0.0,
+                };
+                Vec2::new(
+                    width * self.tile_size.x + pushed,
+                    (height - 1.0) * self.row_height() + self.tile_size.y,
+                )
+            }
+        }
+    }
+
+    // Turns a position in Tiled's pixels into a world position.
+    pub fn world_position(&self, pixel: Vec2) -> Vec2 {
+        let size = self.size();
+        Vec2::new(pixel.x - size.x / 2.0, size.y / 2.0 - pixel.y)
+    }
+
+    // The world position of the map's parallax origin, the top left corner of the map by default.
+    pub fn parallax_origin(&self) -> Vec2 {
+        self.world_position(self.parallax_origin)
+    }
+
+    // The world position of the center of a tile, in Tiled's tile coordinates.
+    pub fn tile_center(&self, x: i32, y: i32) -> Vec2 {
+        let (x, y, tile_size) = (x as f32, y as f32, self.tile_size);
+
+        let pixel = match self.orientation {
+            tiled::Orientation::Orthogonal => (Vec2::new(x, y) + 0.5) * tile_size,
+            tiled::Orientation::Isometric => Vec2::new(
+                (x - y + self.height as f32) * tile_size.x / 2.0,
+                (x + y + 1.0) * tile_size.y / 2.0,
+            ),
+            tiled::Orientation::Staggered | tiled::Orientation::Hexagonal => {
+                let pushed = match self.is_pushed_right(y as i32) {
+                    true => 0.5,
+                    false => 0.0,
+                };
+                Vec2::new(
+                    (x + pushed + 0.5) * tile_size.x,
+                    y * self.row_height() + tile_size.y / 2.0,
+                )
+            }
+        };
+
+        self.world_position(pixel)
+    }
+
+    // Isometric maps measure object positions along the two tile axes, in units of the tile
+    // height, so they are projected like tiles.
+    pub fn object_position(&self, position: Vec2) -> Vec2 {
+        match self.orientation {
+            tiled::Orientation::Isometric => {
+                let tile = position / self.tile_size.y - 0.5;
+                let (x_axis, y_axis) = (
+                    self.tile_center(1, 0) - self.tile_center(0, 0),
+                    self.tile_center(0, 1) - self.tile_center(0, 0),
+                );
+                self.tile_center(0, 0) + x_axis * tile.x + y_axis * tile.y
+            }
+            _ => self.world_position(position),
+        }
+    }
+
+    pub fn map_type(&self, region: &LayerRegion) -> TilemapType {
+        match self.orientation {
+            tiled::Orientation::Orthogonal => TilemapType::Square,
+            tiled::Orientation::Isometric => TilemapType::Isometric(IsoCoordSystem::Diamond),
+            tiled::Orientation::Staggered => TilemapType::Isometric(IsoCoordSystem::Staggered),
+            // The bottom row of the tilemap is the last row of the region.
+            tiled::Orientation::Hexagonal => {
+                match self.is_pushed_right(region.min_y + region.size.y as i32 - 1) {
+                    true => TilemapType::Hexagon(HexCoordSystem::RowEven),
+                    false => TilemapType::Hexagon(HexCoordSystem::RowOdd),
+                }
+            }
+        }
+    }
+
+    // The size of the tilemap a layer region is put in.
+    pub fn tilemap_size(&self, region: &LayerRegion) -> TilemapSize {
+        match self.orientation {
+            tiled::Orientation::Staggered => TilemapSize {
+                x: region.size.x + region.size.y / 2,
+                y: region.size.y,
+            },
+            _ => region.size,
+        }
+    }
+
+    // Where a tile of a layer region goes in its tilemap, from Tiled's tile coordinates.
+    pub fn tile_pos(&self, region: &LayerRegion, x: i32, y: i32) -> TilePos {
+        let (x, y) = ((x - region.min_x) as u32, (y - region.min_y) as u32);
+        let flipped_y = region.size.y - 1 - y;
+
+        match self.orientation {
+            // Each row of a staggered tilemap is half a tile further right than the one below, so
+            // rows are pushed back left by a tile every other row.
+            tiled::Orientation::Staggered => {
+                let pushed = |y: u32| self.is_pushed_right(region.min_y + y as i32) as u32;
+                TilePos {
+                    x: x + (y + pushed(y) - pushed(0)) / 2,
+                    y: flipped_y,
+                }
+            }
+            _ => TilePos { x, y: flipped_y },
+        }
+    }
+
+    // Places a tilemap so that its tiles land on their Tiled tile.
+    pub fn tilemap_transform(&self, region: &LayerRegion, z: f32) -> Transform {
+        let (x, y) = (region.min_x, region.min_y);
+        let tile_pos = self.tile_pos(region, x, y);
+        let translation = self.tile_center(x, y)
+            - tile_pos.center_in_world(&self.grid_size(), &self.map_type(region));
+
+        Transform::from_translation(translation.extend(z))
+    }
+}
+
+// The part of a tile layer that is turned into a tilemap, in Tiled's tile coordinates.
+pub struct LayerRegion {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub size: TilemapSize,
+}
+
+impl LayerRegion {
+    // The smallest region covering both regions.
+    pub fn union(&self, other: &LayerRegion) -> LayerRegion {
+        let max_x = (self.min_x + self.size.x as i32).max(other.min_x + other.size.x as i32);
+        let max_y = (self.min_y + self.size.y as i32).max(other.min_y + other.size.y as i32);
+        let (min_x, min_y) = (self.min_x.min(other.min_x), self.min_y.min(other.min_y));
+
+        LayerRegion {
+            min_x,
+            min_y,
+            size: TilemapSize {
+                x: (max_x - min_x) as u32,
+                y: (max_y - min_y) as u32,
+            },
+        }
+    }
+}
+
+pub fn layer_region(map: &tiled::Map, tile_layer: &tiled::TileLayer) -> Option<LayerRegion> {
+    let tiled::TileLayer::Infinite(layer_data) = tile_layer else {
+        return Some(LayerRegion {
+            min_x: 0,
+            min_y: 0,
+            size: TilemapSize {
+                x: map.width,
+                y: map.height,
+            },
+        });
+    };
+
+    let chunk_width = tiled::Chunk::WIDTH as i32;
+    let chunk_height = tiled::Chunk::HEIGHT as i32;
+
+    let mut bounds: Option<(i32, i32, i32, i32)> = None;
+    for ((chunk_x, chunk_y), _) in layer_data.chunks() {
+        let (min_x, min_y) = (chunk_x * chunk_width, chunk_y * chunk_height);
+        let (max_x, max_y) = (min_x + chunk_width - 1, min_y + chunk_height - 1);
+
+        bounds = Some(match bounds {
+            Some((x0, y0, x1, y1)) => (x0.min(min_x), y0.min(min_y), x1.max(max_x), y1.max(max_y)),
+            None => (min_x, min_y, max_x, max_y),
+        });
+    }
+
+    let (min_x, min_y, max_x, max_y) = bounds?;
+    Some(LayerRegion {
+        min_x,
+        min_y,
+        size: TilemapSize {
+            x: (max_x - min_x + 1) as u32,
+            y: (max_y - min_y + 1) as u32,
+        },
+    })
+}
+
+// Reads an attribute of the `map` element of a TMX file.
+fn map_attribute<'a>(tmx: &'a str, name: &str) -> Option<&'a str> {
+    let start = tmx.find("<map ")?;
+    let element = &tmx[start..start + tmx[start..].find('>')?];
+    let pattern = format!(" {name}=\"");
+    let value = &element[element.find(&pattern)? + pattern.len()..];
+
+    Some(&value[..value.find('"')?])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>"#;
+
+    #[test]
+    fn reads_map_attributes() {
+        let tmx = format!(
+            r#"{HEADER}<map orientation="hexagonal" hexsidelength="8" staggeraxis="y" staggerindex="even"><tileset source="a.tsx"/></map>"#
+        );
+
+        assert_eq!(map_attribute(&tmx, "hexsidelength"), Some("8"));
+        assert_eq!(map_attribute(&tmx, "staggerindex"), Some("even"));
+        assert_eq!(map_attribute(&tmx, "source"), None);
+    }
+
+    fn load(tmx: &str) -> Result<MapGrid> {
+        let tmx = format!(
+            r#"{HEADER}<map version="1.9" {tmx} renderorder="right-down" width="4" height="3" tilewidth="16" tileheight="16" infinite="0" nextlayerid="1" nextobjectid="1"></map>"#
+        );
+        let map = tiled::Loader::new()
+            .load_tmx_map_from(tmx.as_bytes(), "test.tmx")
+            .unwrap();
+
+        MapGrid::new(&map, tmx.as_bytes())
+    }
+
+    #[test]
+    fn rejects_maps_bevy_ecs_tilemap_cannot_draw() {
+        assert!(load(r#"orientation="hexagonal" hexsidelength="8" staggeraxis="y""#).is_ok());
+        assert!(load(r#"orientation="hexagonal" hexsidelength="6" staggeraxis="y""#).is_err());
+        assert!(load(r#"orientation="hexagonal" hexsidelength="8" staggeraxis="x""#).is_err());
+        assert!(load(r#"orientation="staggered" staggeraxis="x""#).is_err());
+    }
+
+    #[test]
+    fn reads_parallax_origin() {
+        let grid = load(r#"orientation="orthogonal""#).unwrap();
+        assert_eq!(grid.parallax_origin(), Vec2::new(-32.0, 24.0));
+
+        let grid =
+            load(r#"orientation="orthogonal" parallaxoriginx="16" parallaxoriginy="-8""#).unwrap();
+        assert_eq!(grid.parallax_origin(), Vec2::new(-16.0, 32.0));
+    }
+
+    #[test]
+    fn pushes_even_rows_when_staggering_even_rows() {
+        let grid = load(r#"orientation="staggered" staggeraxis="y" staggerindex="even""#).unwrap();
+
+        // The bounding box is 72x32 pixels, with row 0 pushed half a tile to the right.
+        assert_eq!(grid.tile_center(0, 0), Vec2::new(-20.0, 8.0));
+        assert_eq!(grid.tile_center(0, 1), Vec2::new(-28.0, 0.0));
+    }
+}
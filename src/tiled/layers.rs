@@ -0,0 +1,113 @@
+use bevy::prelude::{Color, Vec2};
+
+// The distance between the z of two consecutive layers. Layers below the characters stay under
+// `ABOVE_CHARACTERS_Z`, which leaves room for the characters in between.
+pub const LAYER_Z_STEP: f32 = 0.01;
+pub const ABOVE_CHARACTERS_Z: f32 = 10.0;
+
+// A layer of the map with the offset, opacity, visibility, tint and parallax of its parent group
+// layers applied, so groups can be flattened into a single list in draw order.
+pub struct FlatLayer<'map> {
+    pub layer: tiled::Layer<'map>,
+    // The position of the layer in draw order, counting the layers inside groups.
+    pub index: usize,
+    // The offset in pixels, with y pointing down like in Tiled.
+    pub offset: Vec2,
+    pub opacity: f32,
+    pub visible: bool,
+    pub tint: Color,
+    pub parallax: Vec2,
+    // Set with the `above_characters` bool property, for roofs and arches.
+    pub above_characters: bool,
+    // Set with the `y_sort` bool property, for tall tiles like pillars and trees that characters
+    // can walk behind.
+    pub y_sort: bool,
+}
+
+impl<'map> FlatLayer<'map> {
+    fn new(layer: tiled::Layer<'map>, index: usize, parent: Option<&FlatLayer>) -> Self {
+        let tint = match layer.tint_color {
+            Some(tint) => Color::rgba_u8(tint.red, tint.green, tint.blue, tint.alpha),
+            None => Color::WHITE,
+        };
+        let above_characters = bool_property(&layer, "above_characters");
+        let y_sort = bool_property(&layer, "y_sort");
+
+        let mut flat_layer = Self {
+            index,
+            offset: Vec2::new(layer.offset_x, layer.offset_y),
+            opacity: layer.opacity,
+            visible: layer.visible,
+            tint,
+            parallax: Vec2::new(layer.parallax_x, layer.parallax_y),
+            above_characters,
+            y_sort,
+            layer,
+        };
+
+        if let Some(parent) = parent {
+            flat_layer.offset += parent.offset;
+            flat_layer.opacity *= parent.opacity;
+            flat_layer.visible &= parent.visible;
+            flat_layer.tint = Color::rgba(
+                tint.r() * parent.tint.r(),
+                tint.g() * parent.tint.g(),
+                tint.b() * parent.tint.b(),
+                tint.a() * parent.tint.a(),
+            );
+            flat_layer.parallax *= parent.parallax;
+            flat_layer.above_characters |= parent.above_characters;
+            flat_layer.y_sort |= parent.y_sort;
+        }
+
+        flat_layer
+    }
+
+    // The tint of the layer with its opacity applied.
+    pub fn color(&self) -> Color {
+        let mut color = self.tint;
+        color.set_a(self.tint.a() * self.opacity);
+        color
+    }
+
+    pub fn z(&self) -> f32 {
+        let z = self.index as f32 * LAYER_Z_STEP;
+        match self.above_characters {
+            true => ABOVE_CHARACTERS_Z + z,
+            false => z,
+        }
+    }
+}
+
+fn bool_property(layer: &tiled::Layer, name: &str) -> bool {
+    matches!(
+        layer.properties.get(name),
+        Some(tiled::PropertyValue::BoolValue(true))
+    )
+}
+
+// Returns every non-group layer of the map in draw order.
+pub fn flatten_layers(map: &tiled::Map) -> Vec<FlatLayer<'_>> {
+    let mut flat_layers = Vec::new();
+    for layer in map.layers() {
+        flatten_layer(layer, None, &mut flat_layers);
+    }
+    flat_layers
+}
+
+fn flatten_layer<'map>(
+    layer: tiled::Layer<'map>,
+    parent: Option<&FlatLayer>,
+    flat_layers: &mut Vec<FlatLayer<'map>>,
+) {
+    let flat_layer = FlatLayer::new(layer, flat_layers.len(), parent);
+
+    match flat_layer.layer.layer_type() {
+        tiled::LayerType::GroupLayer(group_layer) => {
+            for child in group_layer.layers() {
+                flatten_layer(child, Some(&flat_layer), flat_layers);
+            }
+        }
+        _ => flat_layers.push(flat_layer),
+    }
+}
@@ -0,0 +1,1226 @@
+// How to use this:
+//   You should copy/paste this into your project and use it much like examples/tiles.rs uses this
+//   file. When you do so you will need to adjust the code based on whether you're using the
+//   'atlas` feature in bevy_ecs_tilemap. The bevy_ecs_tilemap uses this as an example of how to
+//   use both single image tilesets and image collection tilesets. Since your project won't have
+//   the 'atlas' feature defined in your Cargo config, the expressions prefixed by the #[cfg(...)]
+//   macro will not compile in your project as-is. If your project depends on the bevy_ecs_tilemap
+//   'atlas' feature then move all of the expressions prefixed by #[cfg(not(feature = "atlas"))].
+//   Otherwise remove all of the expressions prefixed by #[cfg(feature = "atlas")].
+//
+// Functional limitations:
+//   * When the 'atlas' feature is enabled tilesets using a collection of images will be skipped.
+//   * Infinite tile layers are loaded as one tilemap spanning all of their chunks.
+//   * Image layers are spawned as sprites. Layers with a parallax factor follow the camera and
+//     don't get wall colliders.
+//   * Group layers are flattened, their children inherit the group's offset, opacity, visibility,
+//     tint and parallax factor. Layers with the `above_characters` property are drawn over the
+//     characters, see `layers.rs`.
+//   * Tiles on layers with the `y_sort` property are drawn as sprites sorted with the characters.
+//     Their animations and diagonal flips are not applied.
+//   * Tile animations are played by `animate_tiles`, see `animation.rs`.
+//   * Tile collision shapes are converted into rapier colliders, see `collision.rs`.
+//   * Staggered and hexagonal maps must stagger rows, and hexagons need sides half as long as the
+//     tiles are tall, see `grid.rs`.
+//   * Objects are spawned through the spawners registered in `TiledObjectSpawners`, keyed by the
+//     object's class. Objects with an unregistered class only get a `TiledObject` component.
+
+use std::{io::BufReader, time::Duration};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadedAsset},
+    ecs::system::EntityCommands,
+    log,
+    math::Rect,
+    prelude::{
+        AddAsset, Added, App, AssetEvent, Assets, BuildChildren, Bundle, Commands, Component,
+        CoreStage, DespawnRecursiveExt, Entity, EventReader, GlobalTransform, Handle, Image,
+        IntoSystemDescriptor, Name, Plugin, Query, Res, Resource, SpatialBundle, Sprite,
+        SpriteBundle, Transform, Vec2, Visibility,
+    },
+    reflect::TypeUuid,
+    sprite::Anchor,
+    transform::TransformSystem,
+    utils::HashMap,
+};
+use bevy_ecs_tilemap::prelude::*;
+
+use anyhow::Result;
+use bevy_rapier2d::prelude::{ActiveEvents, Collider, RigidBody};
+
+mod animation;
+mod collision;
+mod grid;
+mod layers;
+mod parallax;
+mod properties;
+mod y_sort;
+
+pub use animation::{animate_tiles, AnimationFrame, TiledAnimation};
+use grid::layer_region;
+pub use grid::{LayerRegion, MapGrid};
+pub use layers::flatten_layers;
+pub use parallax::{update_parallax, Parallax};
+pub use properties::TileProperties;
+pub use y_sort::{y_sort, YSort, Y_SORT_MAX_Z};
+
+#[derive(Default)]
+pub struct TiledMapPlugin;
+
+impl Plugin for TiledMapPlugin {
+    fn build(&self, app: &mut bevy::prelude::App) {
+        app.add_asset::<TiledMap>()
+            .add_asset_loader(TiledLoader)
+            .init_resource::<TiledObjectSpawners>()
+            .add_system(process_loaded_maps)
+            .add_system(animate_tiles)
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                update_parallax.before(TransformSystem::TransformPropagate),
+            )
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                y_sort
+                    .after(update_parallax)
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system(add_collision);
+    }
+}
+
+#[derive(TypeUuid, Debug)]
+#[uuid = "e51081d0-6168-4881-a1c6-4249b2000d7f"]
+pub struct TiledMap {
+    pub map: tiled::Map,
+
+    // Where the tiles of the map are placed in the world.
+    pub grid: MapGrid,
+
+    pub tilemap_textures: HashMap<usize, TilemapTexture>,
+
+    // The offset into the tileset_images for each tile id within each tileset.
+    #[cfg(not(feature = "atlas"))]
+    pub tile_image_offsets: HashMap<(usize, tiled::TileId), u32>,
+
+    // The parsed properties of every tile that has any, keyed like `tile_image_offsets`.
+    pub tile_properties: HashMap<(usize, tiled::TileId), TileProperties>,
+
+    // The parsed properties of every tile layer, keyed by layer id.
+    pub layer_properties: HashMap<u32, TileProperties>,
+
+    // The image of every image layer, keyed by layer id.
+    pub image_layer_textures: HashMap<u32, Handle<Image>>,
+}
+
+impl TiledMap {
+    // Returns the index of a tileset's tile within the texture used for its tilemap.
+    pub fn texture_index(
+        &self,
+        tilemap_texture: &TilemapTexture,
+        tileset_index: usize,
+        tile_id: tiled::TileId,
+    ) -> u32 {
+        match tilemap_texture {
+            TilemapTexture::Single(_) => tile_id,
+            #[cfg(not(feature = "atlas"))]
+            TilemapTexture::Vector(_) => *self
+                .tile_image_offsets
+                .get(&(tileset_index, tile_id))
+                .expect(
+                "The offset into to image vector should have been saved during the initial load.",
+            ),
+            #[cfg(not(feature = "atlas"))]
+            _ => unreachable!(),
+        }
+    }
+
+    // Returns the properties of a tile placed on the given layer.
+    pub fn tile_properties(
+        &self,
+        tileset_index: usize,
+        tile_id: tiled::TileId,
+        layer_id: u32,
+    ) -> TileProperties {
+        let tile_properties = self
+            .tile_properties
+            .get(&(tileset_index, tile_id))
+            .cloned()
+            .unwrap_or_default();
+
+        match self.layer_properties.get(&layer_id) {
+            Some(layer_properties) => tile_properties.or_layer(layer_properties),
+            None => tile_properties,
+        }
+    }
+
+    // Marks the cells that block movement on any tile layer, row by row from the bottom like the
+    // tilemaps. The cells cover every chunk of infinite layers. Tiles with collision shapes block
+    // their whole cell. Layers with a parallax factor don't get walls, so they are skipped.
+    pub fn solid_tiles(&self) -> (LayerRegion, Vec<bool>) {
+        let layers = flatten_layers(&self.map);
+        let tile_layers = layers
+            .iter()
+            .filter(|flat_layer| flat_layer.parallax == Vec2::ONE)
+            .filter_map(|flat_layer| match flat_layer.layer.layer_type() {
+                tiled::LayerType::TileLayer(tile_layer) => Some((flat_layer, tile_layer)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let region = tile_layers
+            .iter()
+            .filter_map(|(_, tile_layer)| layer_region(&self.map, tile_layer))
+            .reduce(|region, other| region.union(&other))
+            .unwrap_or(LayerRegion {
+                min_x: 0,
+                min_y: 0,
+                size: TilemapSize {
+                    x: self.map.width,
+                    y: self.map.height,
+                },
+            });
+        let mut solid = vec![false; (region.size.x * region.size.y) as usize];
+
+        for (flat_layer, tile_layer) in tile_layers.iter() {
+            for (column, x) in (region.min_x..).take(region.size.x as usize).enumerate() {
+                for (row, y) in (region.min_y..).take(region.size.y as usize).enumerate() {
+                    let layer_tile = match tile_layer {
+                        tiled::TileLayer::Finite(layer_data) => layer_data.get_tile(x, y),
+                        tiled::TileLayer::Infinite(layer_data) => layer_data.get_tile(x, y),
+                    };
+                    let Some(layer_tile) = layer_tile else {
+                        continue;
+                    };
+
+                    let has_collision = layer_tile.get_tile().is_some_and(|tile| {
+                        tile.collision
+                            .as_ref()
+                            .is_some_and(|collision| !collision.object_data().is_empty())
+                    });
+                    let properties = self.tile_properties(
+                        layer_tile.tileset_index(),
+                        layer_tile.id(),
+                        flat_layer.layer.id(),
+                    );
+
+                    if has_collision || properties.is_solid() {
+                        let row = region.size.y as usize - 1 - row;
+                        solid[row * region.size.x as usize + column] = true;
+                    }
+                }
+            }
+        }
+
+        (region, solid)
+    }
+}
+
+// Stores the entities spawned for each layer, keyed by layer index. Tile layers get one tilemap
+// per tileset they use.
+#[derive(Component, Default)]
+pub struct TiledLayersStorage {
+    pub storage: HashMap<u32, Vec<Entity>>,
+}
+
+#[derive(Default, Bundle)]
+pub struct TiledMapBundle {
+    pub tiled_map: Handle<TiledMap>,
+    pub storage: TiledLayersStorage,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+// A Tiled object spawned from an object layer, along with its custom properties.
+#[derive(Component, Debug, Clone)]
+pub struct TiledObject {
+    pub id: u32,
+    pub name: String,
+    pub class: String,
+    pub size: Vec2,
+    pub properties: tiled::Properties,
+}
+
+impl TiledObject {
+    pub fn get_property(&self, name: &str) -> Option<&tiled::PropertyValue> {
+        self.properties.get(name)
+    }
+}
+
+pub type TiledObjectSpawner = fn(&mut EntityCommands, &TiledObject);
+
+// Stores the spawner callback for each Tiled object class.
+#[derive(Resource, Default)]
+pub struct TiledObjectSpawners {
+    spawners: HashMap<String, TiledObjectSpawner>,
+}
+
+impl TiledObjectSpawners {
+    pub fn register(&mut self, class: impl Into<String>, spawner: TiledObjectSpawner) {
+        self.spawners.insert(class.into(), spawner);
+    }
+
+    pub fn get(&self, class: &str) -> Option<&TiledObjectSpawner> {
+        self.spawners.get(class)
+    }
+}
+
+pub trait TiledObjectAppExt {
+    fn register_tiled_object(
+        &mut self,
+        class: impl Into<String>,
+        spawner: TiledObjectSpawner,
+    ) -> &mut Self;
+}
+
+impl TiledObjectAppExt for App {
+    fn register_tiled_object(
+        &mut self,
+        class: impl Into<String>,
+        spawner: TiledObjectSpawner,
+    ) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(TiledObjectSpawners::default)
+            .register(class, spawner);
+        self
+    }
+}
+
+pub struct TiledLoader;
+
+impl AssetLoader for TiledLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut bevy::asset::LoadContext,
+    ) -> bevy::asset::BoxedFuture<'a, Result<()>> {
+        Box::pin(async move {
+            // The load context path is the TMX file itself. If the file is at the root of the
+            // assets/ directory structure then the tmx_dir will be empty, which is fine.
+            let tmx_dir = load_context
+                .path()
+                .parent()
+                .expect("The asset load context was empty.");
+
+            let mut loader = tiled::Loader::new();
+            let map = loader
+                .load_tmx_map_from(BufReader::new(bytes), load_context.path())
+                .map_err(|e| anyhow::anyhow!("Could not load TMX map: {e}"))?;
+            let grid = MapGrid::new(&map, bytes)
+                .map_err(|e| anyhow::anyhow!("Unsupported TMX map: {e}"))?;
+
+            let mut dependencies = Vec::new();
+            let mut tilemap_textures = HashMap::default();
+            #[cfg(not(feature = "atlas"))]
+            let mut tile_image_offsets = HashMap::default();
+            let mut tile_properties = HashMap::default();
+            let mut layer_properties = HashMap::default();
+
+            for (tileset_index, tileset) in map.tilesets().iter().enumerate() {
+                for (tile_id, tile) in tileset.tiles() {
+                    if tile.properties.is_empty() {
+                        continue;
+                    }
+
+                    let properties = TileProperties::parse(&tile.properties).map_err(|e| {
+                        anyhow::anyhow!("Invalid tile {tile_id} in tileset '{}': {e}", tileset.name)
+                    })?;
+                    tile_properties.insert((tileset_index, tile_id), properties);
+                }
+
+                let tilemap_texture = match &tileset.image {
+                    None => {
+                        #[cfg(feature = "atlas")]
+                        {
+                            log::info!("Skipping image collection tileset '{}' which is incompatible with atlas feature", tileset.name);
+                            continue;
+                        }
+
+                        #[cfg(not(feature = "atlas"))]
+                        {
+                            let mut tile_images: Vec<Handle<Image>> = Vec::new();
+                            for (tile_id, tile) in tileset.tiles() {
+                                if let Some(img) = &tile.image {
+                                    let tile_path = tmx_dir.join(&img.source);
+                                    let asset_path = AssetPath::new(tile_path, None);
+                                    log::info!("Loading tile image from {asset_path:?} as image ({tileset_index}, {tile_id})");
+                                    let texture: Handle<Image> =
+                                        load_context.get_handle(asset_path.clone());
+                                    tile_image_offsets
+                                        .insert((tileset_index, tile_id), tile_images.len() as u32);
+                                    tile_images.push(texture.clone());
+                                    dependencies.push(asset_path);
+                                }
+                            }
+
+                            TilemapTexture::Vector(tile_images)
+                        }
+                    }
+                    Some(img) => {
+                        let tile_path = tmx_dir.join(&img.source);
+                        let asset_path = AssetPath::new(tile_path, None);
+                        let texture: Handle<Image> = load_context.get_handle(asset_path.clone());
+                        dependencies.push(asset_path);
+
+                        TilemapTexture::Single(texture.clone())
+                    }
+                };
+
+                tilemap_textures.insert(tileset_index, tilemap_texture);
+            }
+
+            let mut image_layer_textures = HashMap::default();
+
+            for flat_layer in flatten_layers(&map) {
+                let layer = &flat_layer.layer;
+                if let tiled::LayerType::ImageLayer(image_layer) = layer.layer_type() {
+                    if let Some(img) = &image_layer.image {
+                        let image_path = tmx_dir.join(&img.source);
+                        let asset_path = AssetPath::new(image_path, None);
+                        let texture: Handle<Image> = load_context.get_handle(asset_path.clone());
+                        dependencies.push(asset_path);
+
+                        image_layer_textures.insert(layer.id(), texture);
+                    }
+                    continue;
+                }
+
+                if !matches!(layer.layer_type(), tiled::LayerType::TileLayer(_)) {
+                    continue;
+                }
+
+                let properties = TileProperties::parse(&layer.properties)
+                    .map_err(|e| anyhow::anyhow!("Invalid layer '{}': {e}", layer.name))?;
+                layer_properties.insert(layer.id(), properties);
+            }
+
+            let asset_map = TiledMap {
+                map,
+                grid,
+                tilemap_textures,
+                #[cfg(not(feature = "atlas"))]
+                tile_image_offsets,
+                tile_properties,
+                layer_properties,
+                image_layer_textures,
+            };
+
+            log::info!("Loaded map: {}", load_context.path().display());
+
+            let loaded_asset = LoadedAsset::new(asset_map);
+            load_context.set_default_asset(loaded_asset.with_dependencies(dependencies));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        static EXTENSIONS: &[&str] = &["tmx"];
+        EXTENSIONS
+    }
+}
+
+#[derive(Debug, Component)]
+pub struct Wall;
+
+pub fn process_loaded_maps(
+    mut commands: Commands,
+    mut map_events: EventReader<AssetEvent<TiledMap>>,
+    maps: Res<Assets<TiledMap>>,
+    object_spawners: Res<TiledObjectSpawners>,
+    tile_storage_query: Query<&TileStorage>,
+    mut map_query: Query<(&Handle<TiledMap>, &mut TiledLayersStorage)>,
+    new_maps: Query<&Handle<TiledMap>, Added<Handle<TiledMap>>>,
+) {
+    let mut changed_maps = Vec::<Handle<TiledMap>>::default();
+    let mut removed_maps = Vec::<Handle<TiledMap>>::default();
+    for event in map_events.iter() {
+        match event {
+            AssetEvent::Created { handle } => {
+                log::info!("Map added!");
+                changed_maps.push(handle.clone());
+            }
+            AssetEvent::Modified { handle } => {
+                log::info!("Map changed!");
+                changed_maps.push(handle.clone());
+            }
+            AssetEvent::Removed { handle } => {
+                log::info!("Map removed!");
+                // if mesh was modified and removed in the same update, ignore the modification
+                // events are ordered so future modification events are ok
+                changed_maps.retain(|changed_handle| changed_handle != handle);
+                removed_maps.push(handle.clone_weak());
+            }
+        }
+    }
+
+    for removed_map in removed_maps.iter() {
+        for (map_handle, mut layer_storage) in map_query.iter_mut() {
+            if map_handle == removed_map {
+                despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+            }
+        }
+    }
+
+    // If we have new map entities add them to the changed_maps list.
+    for new_map_handle in new_maps.iter() {
+        changed_maps.push(new_map_handle.clone_weak());
+    }
+
+    for changed_map in changed_maps.iter() {
+        for (map_handle, mut layer_storage) in map_query.iter_mut() {
+            // only deal with currently changed map
+            if map_handle != changed_map {
+                continue;
+            }
+            if let Some(tiled_map) = maps.get(map_handle) {
+                // Clear out the previous version of the map before rebuilding it.
+                despawn_layers(&mut commands, &mut layer_storage, &tile_storage_query);
+
+                // The TilemapBundle requires that all tile images come exclusively from a single
+                // tiled texture or from a Vec of independent per-tile images. Furthermore, all of
+                // the per-tile images must be the same size. Since Tiled allows tiles of mixed
+                // tilesets on each layer and allows differently-sized tile images in each tileset,
+                // this means we need to load each combination of tileset and layer separately.
+
+                // Group layers are flattened, passing their attributes down to their children.
+                let layers = flatten_layers(&tiled_map.map);
+
+                for (tileset_index, tileset) in tiled_map.map.tilesets().iter().enumerate() {
+                    let Some(tilemap_texture) = tiled_map.tilemap_textures.get(&tileset_index)
+                    else {
+                        log::warn!("Skipped creating layer with missing tilemap textures.");
+                        continue;
+                    };
+
+                    let tile_size = TilemapTileSize {
+                        x: tileset.tile_width as f32,
+                        y: tileset.tile_height as f32,
+                    };
+
+                    let tile_spacing = TilemapSpacing {
+                        x: tileset.spacing as f32,
+                        y: tileset.spacing as f32,
+                    };
+
+                    // Once materials have been created/added we need to then create the layers.
+                    for flat_layer in layers.iter() {
+                        let (layer_index, layer) = (flat_layer.index, &flat_layer.layer);
+                        let offset_x = flat_layer.offset.x;
+                        let offset_y = flat_layer.offset.y;
+
+                        // Object layers don't depend on a tileset and are spawned once below.
+                        let tiled::LayerType::TileLayer(tile_layer) = layer.layer_type() else {
+                            continue;
+                        };
+
+                        // Infinite layers are loaded as a single tilemap covering all of their
+                        // chunks.
+                        let Some(region) = layer_region(&tiled_map.map, &tile_layer) else {
+                            log::info!("Skipping layer {} because it has no tiles.", layer.id());
+                            continue;
+                        };
+
+                        let map_size = tiled_map.grid.tilemap_size(&region);
+                        let grid_size = tiled_map.grid.grid_size();
+                        let map_type = tiled_map.grid.map_type(&region);
+
+                        let tiles =
+                            region_tiles(&tiled_map.grid, &tile_layer, &region, tileset_index);
+                        let walls = layer_walls(
+                            tiled_map,
+                            layer.id(),
+                            &tiles,
+                            &map_size,
+                            &tile_size,
+                            &grid_size,
+                            &map_type,
+                        );
+
+                        let mut tile_storage = TileStorage::empty(map_size);
+                        let layer_entity = commands.spawn_empty().id();
+
+                        for region_tile in tiles.iter() {
+                            let (layer_tile, tile_pos, flip) =
+                                (&region_tile.tile, region_tile.pos, region_tile.flip);
+                            let texture_index = tiled_map.texture_index(
+                                tilemap_texture,
+                                tileset_index,
+                                layer_tile.id(),
+                            );
+                            let tile = layer_tile.get_tile().unwrap();
+                            let properties = tiled_map.tile_properties(
+                                tileset_index,
+                                layer_tile.id(),
+                                layer.id(),
+                            );
+                            let is_hidden = properties.is_hidden;
+                            let tile_center = tile_pos.center_in_world(&grid_size, &map_type);
+
+                            let texture = match is_hidden {
+                                true => 0,
+                                false => texture_index,
+                            };
+
+                            // Tiles of y-sorted layers can't be drawn by the tilemap, which
+                            // has a single z. They stay in it for their properties.
+                            let y_sorted = flat_layer.y_sort && !is_hidden;
+                            if y_sorted {
+                                let (image, rect) = tile_image(
+                                    tilemap_texture,
+                                    tileset,
+                                    layer_tile.id(),
+                                    texture_index,
+                                );
+                                let tile_sprite = commands
+                                    .spawn((
+                                        SpriteBundle {
+                                            sprite: Sprite {
+                                                color: flat_layer.color(),
+                                                flip_x: flip.x,
+                                                flip_y: flip.y,
+                                                rect,
+                                                // Tiled draws tiles taller than the grid from
+                                                // the bottom of their cell.
+                                                anchor: Anchor::BottomCenter,
+                                                ..Default::default()
+                                            },
+                                            texture: image,
+                                            transform: Transform::from_translation(
+                                                (tile_center - Vec2::new(0.0, grid_size.y / 2.0))
+                                                    .extend(0.0),
+                                            ),
+                                            ..Default::default()
+                                        },
+                                        YSort::default(),
+                                    ))
+                                    .id();
+                                commands.entity(layer_entity).add_child(tile_sprite);
+                            }
+
+                            let mut tile_commands = commands.spawn(TileBundle {
+                                position: tile_pos,
+                                tilemap_id: TilemapId(layer_entity),
+                                texture_index: TileTextureIndex(texture),
+                                flip,
+                                color: TileColor(flat_layer.color()),
+                                visible: TileVisible(!y_sorted),
+                                ..Default::default()
+                            });
+
+                            if let (Some(frames), false) = (&tile.animation, is_hidden) {
+                                let animation = TiledAnimation::new(
+                                    frames
+                                        .iter()
+                                        .map(|frame| AnimationFrame {
+                                            texture_index: tiled_map.texture_index(
+                                                tilemap_texture,
+                                                tileset_index,
+                                                frame.tile_id,
+                                            ),
+                                            duration: Duration::from_millis(frame.duration as u64),
+                                        })
+                                        .collect(),
+                                );
+
+                                // The first frame isn't necessarily the animated tile itself.
+                                if let Some(texture_index) = animation.texture_index() {
+                                    tile_commands.insert(TileTextureIndex(texture_index));
+                                }
+                                tile_commands.insert(animation);
+                            }
+
+                            // Gameplay systems look up the properties of the tiles characters stand on.
+                            if properties != TileProperties::default() {
+                                tile_commands.insert(properties);
+                            }
+
+                            let tile_entity = tile_commands.id();
+                            tile_storage.set(&tile_pos, tile_entity);
+                        }
+
+                        let transform = tiled_map.grid.tilemap_transform(&region, flat_layer.z())
+                            * Transform::from_xyz(offset_x, -offset_y, 0.0);
+
+                        // Layers with a parallax factor move with the camera, so walls couldn't
+                        // stay under their tiles. Those layers are drawn without collision.
+                        let parallax = Parallax::new(
+                            flat_layer.parallax,
+                            transform.translation.truncate(),
+                            tiled_map.grid.parallax_origin(),
+                        );
+                        let LayerWalls { shapes, polylines } = match parallax {
+                            Some(_) if !walls.is_empty() => {
+                                log::warn!(
+                                    "Skipping the walls of layer '{}', which has a parallax factor.",
+                                    layer.name
+                                );
+                                LayerWalls::default()
+                            }
+                            _ => walls,
+                        };
+
+                        // A single compound collider per layer keeps the number of physics bodies
+                        // low, even for large maps.
+                        if !shapes.is_empty() {
+                            let wall_entity = commands
+                                .spawn((
+                                    Name::new(format!("{} Walls", layer.name)),
+                                    Wall,
+                                    RigidBody::Fixed,
+                                    Collider::compound(shapes),
+                                    ActiveEvents::COLLISION_EVENTS,
+                                    SpatialBundle::default(),
+                                ))
+                                .id();
+                            commands.entity(layer_entity).add_child(wall_entity);
+                        }
+
+                        // Polylines can't be part of a compound, so they get their own collider.
+                        for (position, collider) in polylines {
+                            let wall_entity = commands
+                                .spawn((
+                                    Name::new(format!("{} Wall", layer.name)),
+                                    Wall,
+                                    RigidBody::Fixed,
+                                    collider,
+                                    ActiveEvents::COLLISION_EVENTS,
+                                    SpatialBundle::from_transform(Transform::from_translation(
+                                        position.extend(0.0),
+                                    )),
+                                ))
+                                .id();
+                            commands.entity(layer_entity).add_child(wall_entity);
+                        }
+
+                        commands.entity(layer_entity).insert(TilemapBundle {
+                            grid_size,
+                            size: map_size,
+                            storage: tile_storage,
+                            texture: tilemap_texture.clone(),
+                            tile_size,
+                            spacing: tile_spacing,
+                            transform,
+                            map_type,
+                            visibility: Visibility {
+                                is_visible: flat_layer.visible,
+                            },
+                            ..Default::default()
+                        });
+                        // .spawn(RigidBody::Fixed)
+
+                        if let Some(parallax) = parallax {
+                            commands.entity(layer_entity).insert(parallax);
+                        }
+
+                        layer_storage
+                            .storage
+                            .entry(layer_index as u32)
+                            .or_default()
+                            .push(layer_entity);
+                    }
+                }
+
+                for flat_layer in layers.iter() {
+                    let (layer_index, layer) = (flat_layer.index, &flat_layer.layer);
+                    let tiled::LayerType::ImageLayer(_) = layer.layer_type() else {
+                        continue;
+                    };
+
+                    let Some(texture) = tiled_map.image_layer_textures.get(&layer.id()) else {
+                        log::info!("Skipping image layer {} without an image.", layer.id());
+                        continue;
+                    };
+
+                    // Image layers are placed by their top left corner, relative to the top left
+                    // corner of the map.
+                    let position = tiled_map
+                        .grid
+                        .world_position(flat_layer.offset)
+                        .extend(flat_layer.z());
+
+                    let mut layer_commands = commands.spawn((
+                        Name::new(layer.name.clone()),
+                        SpriteBundle {
+                            sprite: Sprite {
+                                color: flat_layer.color(),
+                                anchor: Anchor::TopLeft,
+                                ..Default::default()
+                            },
+                            texture: texture.clone(),
+                            transform: Transform::from_translation(position),
+                            visibility: Visibility {
+                                is_visible: flat_layer.visible,
+                            },
+                            ..Default::default()
+                        },
+                    ));
+
+                    if let Some(parallax) = Parallax::new(
+                        flat_layer.parallax,
+                        position.truncate(),
+                        tiled_map.grid.parallax_origin(),
+                    ) {
+                        layer_commands.insert(parallax);
+                    }
+
+                    layer_storage
+                        .storage
+                        .entry(layer_index as u32)
+                        .or_default()
+                        .push(layer_commands.id());
+                }
+
+                for flat_layer in layers.iter() {
+                    let (layer_index, layer) = (flat_layer.index, &flat_layer.layer);
+                    let tiled::LayerType::ObjectLayer(object_layer) = layer.layer_type() else {
+                        continue;
+                    };
+
+                    let layer_entity = commands
+                        .spawn((
+                            Name::new(layer.name.clone()),
+                            SpatialBundle {
+                                visibility: Visibility {
+                                    is_visible: flat_layer.visible,
+                                },
+                                ..Default::default()
+                            },
+                        ))
+                        .id();
+
+                    for object in object_layer.objects() {
+                        let position = object_world_position(&tiled_map.grid, &object)
+                            + Vec2::new(flat_layer.offset.x, -flat_layer.offset.y);
+
+                        let tiled_object = TiledObject {
+                            id: object.id(),
+                            name: object.name.clone(),
+                            class: object.user_type.clone(),
+                            size: object_size(&object.shape),
+                            properties: object.properties.clone(),
+                        };
+
+                        let mut object_commands = commands.spawn((
+                            Name::new(format!("{} ({})", tiled_object.class, tiled_object.id)),
+                            SpatialBundle::from_transform(Transform::from_xyz(
+                                position.x, position.y, 0.0,
+                            )),
+                        ));
+
+                        match object_spawners.get(&tiled_object.class) {
+                            Some(spawner) => spawner(&mut object_commands, &tiled_object),
+                            None => log::info!(
+                                "No spawner registered for object {} of class '{}'.",
+                                tiled_object.id,
+                                tiled_object.class
+                            ),
+                        }
+
+                        let object_entity = object_commands.insert(tiled_object).id();
+                        commands.entity(layer_entity).add_child(object_entity);
+                    }
+
+                    layer_storage
+                        .storage
+                        .entry(layer_index as u32)
+                        .or_default()
+                        .push(layer_entity);
+                }
+            }
+        }
+    }
+}
+
+// A tile of a layer, along with where it goes in the layer's tilemap.
+struct RegionTile<'a> {
+    pos: TilePos,
+    tile: tiled::LayerTile<'a>,
+    flip: TileFlip,
+}
+
+// The tiles of a layer region that come from the given tileset.
+fn region_tiles<'a>(
+    grid: &MapGrid,
+    tile_layer: &'a tiled::TileLayer,
+    region: &LayerRegion,
+    tileset_index: usize,
+) -> Vec<RegionTile<'a>> {
+    let mut tiles = Vec::new();
+
+    for y in region.min_y..region.min_y + region.size.y as i32 {
+        for x in region.min_x..region.min_x + region.size.x as i32 {
+            let (layer_tile, layer_tile_data) = match tile_layer {
+                tiled::TileLayer::Finite(layer_data) => {
+                    (layer_data.get_tile(x, y), layer_data.get_tile_data(x, y))
+                }
+                tiled::TileLayer::Infinite(layer_data) => {
+                    (layer_data.get_tile(x, y), layer_data.get_tile_data(x, y))
+                }
+            };
+            let (Some(layer_tile), Some(layer_tile_data)) = (layer_tile, layer_tile_data) else {
+                continue;
+            };
+            if layer_tile.tileset_index() != tileset_index {
+                continue;
+            }
+
+            tiles.push(RegionTile {
+                pos: grid.tile_pos(region, x, y),
+                tile: layer_tile,
+                flip: TileFlip {
+                    x: layer_tile_data.flip_h,
+                    y: layer_tile_data.flip_v,
+                    d: layer_tile_data.flip_d,
+                },
+            });
+        }
+    }
+
+    tiles
+}
+
+// The wall colliders of a layer's tilemap, relative to the tilemap.
+#[derive(Default)]
+struct LayerWalls {
+    shapes: Vec<(Vec2, f32, Collider)>,
+    polylines: Vec<(Vec2, Collider)>,
+}
+
+impl LayerWalls {
+    fn is_empty(&self) -> bool {
+        self.shapes.is_empty() && self.polylines.is_empty()
+    }
+}
+
+fn layer_walls(
+    tiled_map: &TiledMap,
+    layer_id: u32,
+    tiles: &[RegionTile],
+    map_size: &TilemapSize,
+    tile_size: &TilemapTileSize,
+    grid_size: &TilemapGridSize,
+    map_type: &TilemapType,
+) -> LayerWalls {
+    let mut walls = LayerWalls::default();
+    let mut solid_tiles = vec![false; (map_size.x * map_size.y) as usize];
+
+    for region_tile in tiles {
+        let Some(tile) = region_tile.tile.get_tile() else {
+            continue;
+        };
+        let properties = tiled_map.tile_properties(
+            region_tile.tile.tileset_index(),
+            region_tile.tile.id(),
+            layer_id,
+        );
+        let (is_rigid, offset) = (properties.is_solid(), properties.offset);
+        let tile_pos = region_tile.pos;
+        let tile_center = tile_pos.center_in_world(grid_size, map_type);
+
+        // Shapes from the tile collision editor take precedence over the plain `rigid` box. Plain
+        // boxes are merged once the layer is done, which only works for square grids.
+        match collision::tile_collider(&tile, tile_size, &region_tile.flip) {
+            Some(colliders) => {
+                walls.shapes.extend(colliders.shapes.into_iter().map(
+                    |(position, rotation, collider)| (tile_center + position, rotation, collider),
+                ));
+                walls.polylines.extend(
+                    colliders
+                        .polylines
+                        .into_iter()
+                        .map(|collider| (tile_center, collider)),
+                );
+            }
+            None if is_rigid && offset == 0 && matches!(map_type, TilemapType::Square) => {
+                solid_tiles[(tile_pos.y * map_size.x + tile_pos.x) as usize] = true;
+            }
+            None if is_rigid && offset == 0 => {
+                if let Some(collider) = collision::tile_footprint(map_type, grid_size) {
+                    walls.shapes.push((tile_center, 0.0, collider));
+                }
+            }
+            None if is_rigid => {
+                walls.shapes.push((
+                    tile_center,
+                    0.0,
+                    Collider::cuboid(tile_size.x / 2.0 + offset as f32, tile_size.y / 2.0),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    walls.shapes.extend(
+        collision::merge_solid_tiles(&solid_tiles, map_size.x, map_size.y)
+            .iter()
+            .map(|rect| rect.collider_shape(grid_size, tile_size)),
+    );
+
+    walls
+}
+
+// The image of a tile and the part of it covered by the tile, for drawing the tile as a sprite.
+fn tile_image(
+    tilemap_texture: &TilemapTexture,
+    tileset: &tiled::Tileset,
+    tile_id: tiled::TileId,
+    texture_index: u32,
+) -> (Handle<Image>, Option<Rect>) {
+    match tilemap_texture {
+        TilemapTexture::Single(texture) => {
+            let columns = tileset.columns.max(1);
+            let (tile_width, tile_height) = (tileset.tile_width as f32, tileset.tile_height as f32);
+            let min = Vec2::new(
+                tileset.margin as f32
+                    + (tile_id % columns) as f32 * (tile_width + tileset.spacing as f32),
+                tileset.margin as f32
+                    + (tile_id / columns) as f32 * (tile_height + tileset.spacing as f32),
+            );
+
+            (
+                texture.clone(),
+                Some(Rect::from_corners(
+                    min,
+                    min + Vec2::new(tile_width, tile_height),
+                )),
+            )
+        }
+        #[cfg(not(feature = "atlas"))]
+        TilemapTexture::Vector(textures) => (textures[texture_index as usize].clone(), None),
+        #[cfg(not(feature = "atlas"))]
+        _ => unreachable!(),
+    }
+}
+
+// Despawns every layer of a map along with its tiles. Wall colliders and objects are children of
+// their layer entity, so they are removed with it. Entities spawned later by the objects, like
+// enemies, aren't tracked here.
+pub fn despawn_layers(
+    commands: &mut Commands,
+    layer_storage: &mut TiledLayersStorage,
+    tile_storage_query: &Query<&TileStorage>,
+) {
+    for layer_entity in layer_storage.storage.values().flatten() {
+        if let Ok(layer_tile_storage) = tile_storage_query.get(*layer_entity) {
+            for tile in layer_tile_storage.iter().flatten() {
+                commands.entity(*tile).despawn_recursive();
+            }
+        }
+        commands.entity(*layer_entity).despawn_recursive();
+    }
+    layer_storage.storage.clear();
+}
+
+fn object_size(shape: &tiled::ObjectShape) -> Vec2 {
+    match shape {
+        tiled::ObjectShape::Rect { width, height }
+        | tiled::ObjectShape::Ellipse { width, height } => Vec2::new(*width, *height),
+        _ => Vec2::ZERO,
+    }
+}
+
+// Objects are placed by their center.
+fn object_world_position(grid: &MapGrid, object: &tiled::ObjectData) -> Vec2 {
+    let size = object_size(&object.shape);
+    grid.object_position(Vec2::new(object.x, object.y) + size / 2.0)
+}
+
+pub fn add_collision(mut commands: Commands) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::load_map;
+
+    // The world positions of the tiles and wall colliders of a tilemap built by
+    // `process_loaded_maps`.
+    struct PlacedTilemap {
+        tiles: Vec<Vec2>,
+        shapes: Vec<Vec2>,
+        polylines: Vec<Vec2>,
+    }
+
+    // The tilemaps `process_loaded_maps` builds for a layer, one per tileset.
+    fn tilemaps(tiled_map: &TiledMap, layer: &tiled::Layer) -> Vec<PlacedTilemap> {
+        let tiled::LayerType::TileLayer(tile_layer) = layer.layer_type() else {
+            return Vec::new();
+        };
+        let Some(region) = layer_region(&tiled_map.map, &tile_layer) else {
+            return Vec::new();
+        };
+        let grid = &tiled_map.grid;
+        let (grid_size, map_type) = (grid.grid_size(), grid.map_type(&region));
+        let transform = grid.tilemap_transform(&region, 0.0);
+        let world = |position: Vec2| transform.transform_point(position.extend(0.0)).truncate();
+
+        tiled_map
+            .map
+            .tilesets()
+            .iter()
+            .enumerate()
+            .map(|(tileset_index, tileset)| {
+                let tile_size = TilemapTileSize {
+                    x: tileset.tile_width as f32,
+                    y: tileset.tile_height as f32,
+                };
+                let tiles = region_tiles(grid, &tile_layer, &region, tileset_index);
+                let walls = layer_walls(
+                    tiled_map,
+                    layer.id(),
+                    &tiles,
+                    &grid.tilemap_size(&region),
+                    &tile_size,
+                    &grid_size,
+                    &map_type,
+                );
+
+                PlacedTilemap {
+                    tiles: tiles
+                        .iter()
+                        .map(|tile| world(tile.pos.center_in_world(&grid_size, &map_type)))
+                        .collect(),
+                    shapes: walls
+                        .shapes
+                        .iter()
+                        .map(|(position, ..)| world(*position))
+                        .collect(),
+                    polylines: walls
+                        .polylines
+                        .iter()
+                        .map(|(position, _)| world(*position))
+                        .collect(),
+                }
+            })
+            .collect()
+    }
+
+    fn assert_positions(path: &str, what: &str, positions: &[Vec2], expected: &[(f32, f32)]) {
+        let expected = expected
+            .iter()
+            .map(|&(x, y)| Vec2::new(x, y))
+            .collect::<Vec<_>>();
+        assert!(
+            positions.len() == expected.len()
+                && positions
+                    .iter()
+                    .zip(&expected)
+                    .all(|(position, expected)| position.abs_diff_eq(*expected, 0.001)),
+            "{path}: {what} are at {positions:?}, expected {expected:?}"
+        );
+    }
+
+    // The fixture maps have a single tileset whose only tile is rigid. Their objects are named
+    // after the tile they are centered on.
+    fn assert_placed(
+        path: &str,
+        tiles: &[(f32, f32)],
+        walls: &[(f32, f32)],
+        objects: &[(f32, f32)],
+    ) {
+        let tiled_map = load_map(path);
+        let layers = flatten_layers(&tiled_map.map);
+
+        let tilemaps = layers
+            .iter()
+            .flat_map(|flat_layer| tilemaps(&tiled_map, &flat_layer.layer))
+            .collect::<Vec<_>>();
+        assert_eq!(tilemaps.len(), 1);
+        assert_positions(path, "tiles", &tilemaps[0].tiles, tiles);
+        assert_positions(path, "walls", &tilemaps[0].shapes, walls);
+        assert!(tilemaps[0].polylines.is_empty());
+
+        let object_positions = layers
+            .iter()
+            .filter_map(|flat_layer| match flat_layer.layer.layer_type() {
+                tiled::LayerType::ObjectLayer(object_layer) => Some(object_layer),
+                _ => None,
+            })
+            .flat_map(|object_layer| {
+                object_layer
+                    .objects()
+                    .map(|object| object_world_position(&tiled_map.grid, &object))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_positions(path, "objects", &object_positions, objects);
+    }
+
+    #[test]
+    fn places_orthogonal_maps() {
+        // The map is 64x36 pixels. The two tiles of the top row merge into a single wall.
+        assert_placed(
+            "tests/fixtures/orthogonal.tmx",
+            &[(-24.0, 12.0), (-8.0, 12.0), (24.0, -12.0)],
+            &[(24.0, -12.0), (-16.0, 12.0)],
+            &[(8.0, 0.0), (-24.0, -12.0), (24.0, 12.0)],
+        );
+    }
+
+    #[test]
+    fn places_isometric_maps() {
+        // The map is a 112x56 pixel diamond, with the first tile in its top corner.
+        assert_placed(
+            "tests/fixtures/isometric.tmx",
+            &[(-8.0, 20.0), (8.0, -20.0)],
+            &[(-8.0, 20.0), (8.0, -20.0)],
+            &[(8.0, -4.0), (-40.0, 4.0), (40.0, -4.0)],
+        );
+    }
+
+    #[test]
+    fn places_staggered_maps() {
+        // The map is 144x32 pixels, odd rows are pushed half a tile to the right.
+        assert_placed(
+            "tests/fixtures/staggered.tmx",
+            &[(-56.0, 8.0), (24.0, 0.0), (40.0, -8.0)],
+            &[(-56.0, 8.0), (24.0, 0.0), (40.0, -8.0)],
+            &[(24.0, 0.0), (-24.0, -8.0), (-56.0, 8.0)],
+        );
+    }
+
+    #[test]
+    fn places_hexagonal_maps() {
+        // The map is 63x40 pixels, odd rows are pushed half a tile to the right and rows are 12
+        // pixels apart.
+        assert_placed(
+            "tests/fixtures/hexagonal.tmx",
+            &[(-24.5, 12.0), (10.5, 0.0), (17.5, -12.0)],
+            &[(-24.5, 12.0), (10.5, 0.0), (17.5, -12.0)],
+            &[(10.5, 0.0), (-10.5, -12.0), (-24.5, 12.0)],
+        );
+    }
+
+    #[test]
+    fn merges_walls_of_bundled_map() {
+        let tiled_map = load_map("assets/map.tmx");
+
+        let wall_counts = flatten_layers(&tiled_map.map)
+            .iter()
+            .map(|flat_layer| {
+                let tilemaps = tilemaps(&tiled_map, &flat_layer.layer);
+                (
+                    flat_layer.layer.name.clone(),
+                    tilemaps
+                        .iter()
+                        .map(|tilemap| tilemap.shapes.len())
+                        .sum::<usize>(),
+                    tilemaps
+                        .iter()
+                        .map(|tilemap| tilemap.polylines.len())
+                        .sum::<usize>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The top and bottom walls each merge into a single rectangle. The 40 side wall tiles are
+        // hidden tiles with an offset, which keep colliders of their own.
+        assert_eq!(
+            wall_counts,
+            vec![
+                ("Tile Layer 1".to_string(), 42, 0),
+                ("Tile Layer 2".to_string(), 0, 0),
+                ("Tile Layer 3".to_string(), 0, 0),
+                ("Spawns".to_string(), 0, 0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use bevy::prelude::{Camera, Component, Parent, Query, Transform, Vec2, With, Without};
+
+// Moves a layer along with the camera according to its Tiled parallax factor. A factor of 1 moves
+// with the map, 0 stays fixed on screen. Like in Tiled, the layer is at its position in the map
+// while the camera is on the map's parallax origin.
+#[derive(Debug, Component)]
+pub struct Parallax {
+    pub factor: Vec2,
+    pub origin: Vec2,
+    pub camera_origin: Vec2,
+}
+
+impl Parallax {
+    // Returns `None` for layers that scroll with the map.
+    pub fn new(factor: Vec2, origin: Vec2, camera_origin: Vec2) -> Option<Self> {
+        if factor == Vec2::ONE {
+            return None;
+        }
+
+        Some(Self {
+            factor,
+            origin,
+            camera_origin,
+        })
+    }
+}
+
+// Runs before transform propagation, so the camera position is computed from its local transform
+// and the transform of the entity it follows.
+pub fn update_parallax(
+    camera_query: Query<(&Transform, Option<&Parent>), With<Camera>>,
+    parent_query: Query<&Transform, (Without<Camera>, Without<Parallax>)>,
+    mut layer_query: Query<(&Parallax, &mut Transform), Without<Camera>>,
+) {
+    let Some((camera_transform, parent)) = camera_query.iter().next() else {
+        return;
+    };
+
+    let mut camera_position = camera_transform.translation.truncate();
+    if let Some(parent_transform) = parent.and_then(|parent| parent_query.get(parent.get()).ok()) {
+        camera_position += parent_transform.translation.truncate();
+    }
+
+    for (parallax, mut transform) in layer_query.iter_mut() {
+        let position = parallax.origin
+            + (camera_position - parallax.camera_origin) * (Vec2::ONE - parallax.factor);
+        transform.translation.x = position.x;
+        transform.translation.y = position.y;
+    }
+}
@@ -0,0 +1,296 @@
+use anyhow::{anyhow, Result};
+use bevy::prelude::Component;
+use tiled::{Properties, PropertyValue};
+
+// Gameplay properties of a tile, parsed once from the custom properties set in Tiled. The same
+// properties can be set on a tile layer to apply them to every tile on it, except for `rigid`.
+#[derive(Component, Debug, Clone, Default, PartialEq)]
+pub struct TileProperties {
+    // Set on a tile, makes it block movement. Set on a layer, switches the collision of the
+    // layer's rigid tiles on or off rather than making every tile on it rigid.
+    pub rigid: Option<bool>,
+    pub is_hidden: bool,
+    // Extra width added to each side of a rigid tile's collider.
+    pub offset: i32,
+    pub damage_per_second: f32,
+    // The name of the kind of damage dealt by `damage_per_second`, fire when it isn't set.
+    pub damage_kind: Option<String>,
+    pub heal_per_second: f32,
+    // The fraction of walk speed lost while standing on the tile.
+    pub slow: f32,
+    pub footstep_sound: Option<String>,
+    // The name of a status effect applied to characters standing on the tile, with its strength.
+    pub status_effect: Option<String>,
+    pub status_magnitude: f32,
+}
+
+impl TileProperties {
+    pub fn parse(properties: &Properties) -> Result<Self> {
+        Ok(Self {
+            rigid: bool_property(properties, "rigid")?,
+            is_hidden: bool_property(properties, "is_hidden")?.unwrap_or(false),
+            offset: int_property(properties, "offset")?,
+            damage_per_second: float_property(properties, "damage_per_second")?,
+            damage_kind: string_property(properties, "damage_kind")?,
+            heal_per_second: float_property(properties, "heal_per_second")?,
+            slow: float_property(properties, "slow")?,
+            footstep_sound: string_property(properties, "footstep_sound")?,
+            status_effect: string_property(properties, "status_effect")?,
+            status_magnitude: float_property(properties, "status_magnitude")?,
+        })
+    }
+
+    // Combines the properties of a tile with the ones of its layer. Values set on the tile win.
+    pub fn or_layer(&self, layer: &TileProperties) -> TileProperties {
+        TileProperties {
+            rigid: match layer.rigid {
+                Some(false) => Some(false),
+                _ => self.rigid,
+            },
+            is_hidden: self.is_hidden || layer.is_hidden,
+            offset: match self.offset {
+                0 => layer.offset,
+                offset => offset,
+            },
+            damage_per_second: match self.damage_per_second {
+                0.0 => layer.damage_per_second,
+                damage => damage,
+            },
+            damage_kind: self
+                .damage_kind
+                .clone()
+                .or_else(|| layer.damage_kind.clone()),
+            heal_per_second: match self.heal_per_second {
+                0.0 => layer.heal_per_second,
+                heal => heal,
+            },
+            slow: match self.slow {
+                0.0 => layer.slow,
+                slow => slow,
+            },
+            footstep_sound: self
+                .footstep_sound
+                .clone()
+                .or_else(|| layer.footstep_sound.clone()),
+            status_effect: self
+                .status_effect
+                .clone()
+                .or_else(|| layer.status_effect.clone()),
+            status_magnitude: match self.status_magnitude {
+                0.0 => layer.status_magnitude,
+                magnitude => magnitude,
+            },
+        }
+    }
+
+    // Hidden tiles are invisible walls, so they always block movement.
+    pub fn is_solid(&self) -> bool {
+        self.rigid == Some(true) || self.is_hidden
+    }
+}
+
+fn bool_property(properties: &Properties, name: &str) -> Result<Option<bool>> {
+    match properties.get(name) {
+        None => Ok(None),
+        Some(PropertyValue::BoolValue(value)) => Ok(Some(*value)),
+        Some(value) => Err(anyhow!(
+            "Property `{name}` should be a bool, found {value:?}"
+        )),
+    }
+}
+
+fn int_property(properties: &Properties, name: &str) -> Result<i32> {
+    match properties.get(name) {
+        None => Ok(0),
+        Some(PropertyValue::IntValue(value)) => Ok(*value),
+        Some(value) => Err(anyhow!(
+            "Property `{name}` should be an int, found {value:?}"
+        )),
+    }
+}
+
+fn float_property(properties: &Properties, name: &str) -> Result<f32> {
+    match properties.get(name) {
+        None => Ok(0.0),
+        Some(PropertyValue::FloatValue(value)) => Ok(*value),
+        Some(PropertyValue::IntValue(value)) => Ok(*value as f32),
+        Some(value) => Err(anyhow!(
+            "Property `{name}` should be a float, found {value:?}"
+        )),
+    }
+}
+
+fn string_property(properties: &Properties, name: &str) -> Result<Option<String>> {
+    match properties.get(name) {
+        None => Ok(None),
+        Some(PropertyValue::StringValue(value) | PropertyValue::FileValue(value)) => {
+            Ok(Some(value.clone()))
+        }
+        Some(value) => Err(anyhow!(
+            "Property `{name}` should be a string, found {value:?}"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(values: &[(&str, PropertyValue)]) -> Properties {
+        values
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_empty_properties_as_defaults() {
+        let parsed = TileProperties::parse(&Properties::new()).unwrap();
+
+        assert_eq!(parsed, TileProperties::default());
+        assert!(!parsed.is_solid());
+    }
+
+    #[test]
+    fn parses_every_property() {
+        let parsed = TileProperties::parse(&properties(&[
+            ("rigid", PropertyValue::BoolValue(true)),
+            ("is_hidden", PropertyValue::BoolValue(false)),
+            ("offset", PropertyValue::IntValue(4)),
+            ("damage_per_second", PropertyValue::FloatValue(2.5)),
+            (
+                "damage_kind",
+                PropertyValue::StringValue("poison".to_string()),
+            ),
+            ("heal_per_second", PropertyValue::IntValue(3)),
+            ("slow", PropertyValue::FloatValue(0.5)),
+            (
+                "footstep_sound",
+                PropertyValue::FileValue("sounds/stone.ogg".to_string()),
+            ),
+            (
+                "status_effect",
+                PropertyValue::StringValue("burn".to_string()),
+            ),
+            ("status_magnitude", PropertyValue::FloatValue(4.0)),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            TileProperties {
+                rigid: Some(true),
+                is_hidden: false,
+                offset: 4,
+                damage_per_second: 2.5,
+                damage_kind: Some("poison".to_string()),
+                heal_per_second: 3.0,
+                slow: 0.5,
+                footstep_sound: Some("sounds/stone.ogg".to_string()),
+                status_effect: Some("burn".to_string()),
+                status_magnitude: 4.0,
+            }
+        );
+    }
+
+    #[test]
+    fn hidden_tiles_are_solid() {
+        let parsed = TileProperties::parse(&properties(&[(
+            "is_hidden",
+            PropertyValue::BoolValue(true),
+        )]))
+        .unwrap();
+
+        assert_eq!(parsed.rigid, None);
+        assert!(parsed.is_solid());
+    }
+
+    #[test]
+    fn rejects_wrong_property_types() {
+        let wrong_values = [
+            ("rigid", PropertyValue::StringValue("false".to_string())),
+            ("rigid", PropertyValue::IntValue(0)),
+            ("is_hidden", PropertyValue::StringValue("true".to_string())),
+            ("offset", PropertyValue::FloatValue(4.0)),
+            ("damage_per_second", PropertyValue::BoolValue(true)),
+            ("damage_kind", PropertyValue::IntValue(1)),
+            (
+                "heal_per_second",
+                PropertyValue::StringValue("1".to_string()),
+            ),
+            ("slow", PropertyValue::BoolValue(true)),
+            ("footstep_sound", PropertyValue::IntValue(1)),
+            ("status_effect", PropertyValue::BoolValue(true)),
+            (
+                "status_magnitude",
+                PropertyValue::StringValue("2".to_string()),
+            ),
+        ];
+
+        for (name, value) in wrong_values {
+            let error = TileProperties::parse(&properties(&[(name, value.clone())])).unwrap_err();
+
+            assert!(
+                error.to_string().contains(&format!("`{name}`")),
+                "{name} = {value:?} gave: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn tile_values_win_over_layer_values() {
+        let tile = TileProperties {
+            offset: 2,
+            slow: 0.25,
+            footstep_sound: Some("tile".to_string()),
+            ..Default::default()
+        };
+        let layer = TileProperties {
+            offset: 4,
+            slow: 0.5,
+            damage_per_second: 1.0,
+            damage_kind: Some("ice".to_string()),
+            footstep_sound: Some("layer".to_string()),
+            status_effect: Some("poison".to_string()),
+            ..Default::default()
+        };
+
+        let combined = tile.or_layer(&layer);
+
+        assert_eq!(combined.offset, 2);
+        assert_eq!(combined.slow, 0.25);
+        assert_eq!(combined.footstep_sound, Some("tile".to_string()));
+        assert_eq!(combined.damage_per_second, 1.0);
+        assert_eq!(combined.damage_kind, Some("ice".to_string()));
+        assert_eq!(combined.status_effect, Some("poison".to_string()));
+    }
+
+    #[test]
+    fn layer_rigid_switches_tile_collision() {
+        let rigid_tile = TileProperties {
+            rigid: Some(true),
+            ..Default::default()
+        };
+        let floor_tile = TileProperties::default();
+        let layer = |rigid| TileProperties {
+            rigid,
+            ..Default::default()
+        };
+
+        assert!(rigid_tile.or_layer(&layer(None)).is_solid());
+        assert!(rigid_tile.or_layer(&layer(Some(true))).is_solid());
+        assert!(!rigid_tile.or_layer(&layer(Some(false))).is_solid());
+        // A rigid layer doesn't make its floor solid.
+        assert!(!floor_tile.or_layer(&layer(Some(true))).is_solid());
+    }
+
+    #[test]
+    fn hidden_layers_hide_every_tile() {
+        let layer = TileProperties {
+            is_hidden: true,
+            ..Default::default()
+        };
+
+        assert!(TileProperties::default().or_layer(&layer).is_solid());
+    }
+}
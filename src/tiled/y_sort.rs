@@ -0,0 +1,48 @@
+use bevy::prelude::{Component, GlobalTransform, Parent, Query, Transform, Vec3};
+
+// Y-sorted entities are drawn between the layers below the characters and the layers marked
+// `above_characters`, see `layers.rs`.
+pub const Y_SORT_MIN_Z: f32 = 1.0;
+pub const Y_SORT_MAX_Z: f32 = 8.0;
+
+// The distance along y that moves an entity one unit of z.
+const Y_SORT_SCALE: f32 = 1000.0;
+
+// Sets the z of an entity from its world y, so entities lower on screen are drawn in front.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct YSort {
+    // The distance from the entity's origin to the point it's sorted by, usually its feet.
+    pub offset: f32,
+}
+
+impl YSort {
+    pub fn new(offset: f32) -> Self {
+        Self { offset }
+    }
+}
+
+fn y_sort_z(y: f32) -> f32 {
+    ((Y_SORT_MIN_Z + Y_SORT_MAX_Z) / 2.0 - y / Y_SORT_SCALE).clamp(Y_SORT_MIN_Z, Y_SORT_MAX_Z)
+}
+
+// Runs before transform propagation. Children are sorted by their world position, using the
+// global transform of their parent from the previous frame.
+pub fn y_sort(
+    mut query: Query<(&YSort, &mut Transform, Option<&Parent>)>,
+    parent_query: Query<&GlobalTransform>,
+) {
+    for (y_sort, mut transform, parent) in query.iter_mut() {
+        let parent_translation = parent
+            .and_then(|parent| parent_query.get(parent.get()).ok())
+            .map(|parent_transform| parent_transform.translation())
+            .unwrap_or(Vec3::ZERO);
+
+        let y = parent_translation.y + transform.translation.y + y_sort.offset;
+        let z = y_sort_z(y) - parent_translation.z;
+
+        // Avoid marking still entities as changed every frame.
+        if transform.translation.z != z {
+            transform.translation.z = z;
+        }
+    }
+}